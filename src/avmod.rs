@@ -1,43 +1,115 @@
 #![allow(dead_code, unused_mut)]
-use super::media_player::MediaPlayer;
+use super::duplicates::{self, VideoInfo};
+use super::entry_meta::{self, AudioVersion, EntryMeta, MetaStore};
+use super::hangul;
+use super::instrumental::{self, TrackInfo};
+use super::integrity::IntegrityReport;
+use super::loudness;
+use super::lyrics;
+use super::media_player::{
+    DualProcessPlayer, PlayRequest, PlaybackState, PlayerBackend, PlayerControl, PlayerEvent,
+    PlayerStatus,
+};
+use super::mv_name::{AliasTable, ParsedName};
+use super::play_queue::{self, PlayQueue};
+use super::player_command::PlayerProfile;
+use super::probe;
+use super::quality::{self, QualityPolicy};
+use super::release_date::{self, ReleaseDate};
+use super::renamer::{self, NameFields, Rename, RenameError};
+use super::tempo;
+use super::thumbnails::{self, ImageProtocol};
+use super::unicode_paths;
+use super::views::updater;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::thread::JoinHandle;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
 
 type JsonFormat = HashMap<String, String>;
+
+/// An entry whose linked audio is lossy or has a better copy in the audio directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityEntry {
+    pub video_name: String,
+    pub audio_path: String,
+    pub lossy: bool,
+    pub better_copy: Option<String>,
+}
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Sorting {
     Ascending,
     Descending,
     Mtime,
+    ReleaseDate,
+    Bpm,
 }
 
 /// Used to store the data for the media files
 /// # Fields
 /// * `data_file`: The path to the json file that stores the data_file
 /// * `video_dir`: The path to the directory that contains the video files. All videos are assumed to
-/// be in this directory
+///   be in this directory
 /// * `audio_dir`: The path to the directory that contains the audio files. All audio files are
-/// assumed to be in this directory
+///   assumed to be in this directory
 /// * `audio_video`: The data that is stored in the json file. The key is the video file name, and
-/// the value is the audio file path inside the audio directory
+///   the value is the audio file path inside the audio directory
+/// * `meta`: Tags, ratings and favourites per entry. Stored in `meta_file` next to the data file
+/// * `aliases`: Maps artist name variants to a canonical artist for grouping and search
+/// * `cache_dir`: Directory for generated files such as subtitles
+/// * `show_lyrics`: Whether synced lyrics are passed to the video player as subtitles
+/// * `lyrics_notice`: Why the last played video got no subtitle while `show_lyrics` is on
+/// * `subtitle_arg`: Video player argument that the subtitle path is appended to
+/// * `karaoke`: Whether the instrumental version of the linked audio is played when there is one
+/// * `quality_policy`: Which copy of a track is preferred when it exists in several formats
+/// * `trash_dir`: Where duplicate videos are moved to
+/// * `target_loudness`: Loudness in LUFS that playback is levelled to
+/// * `gain_arg`: Audio player argument that applies a gain, `{gain}` is replaced by the gain in dB
+/// * `image_protocol`: How thumbnails are drawn in the preview pane
+/// * `rename_template`: Template that canonical video file names are built from
+/// * `player_profiles`: Player commands for videos with some extensions or categories
+/// * `queue`: Videos lined up to play. Stored in `queue_file` next to the data file
 /// * `video_list`: The list of video file names without the full path
-/// * `player`: The media player that is used to play the media files
+/// * `player`: The backend that plays the media files, two separate player processes by default
+/// * `now_playing`: Path of the video the player was last asked to play
+/// * `player_events`: Finished and stopped events from the player
 pub struct AudioVideoData {
     pub data_file: String,
     pub video_dir: String,
     pub audio_dir: String,
     pub audio_video: Arc<RefCell<JsonFormat>>,
+    pub meta_file: String,
+    pub meta: Arc<RefCell<MetaStore>>,
     pub video_list: Option<Vec<String>>,
     pub search_filtered_list: Option<Vec<String>>,
     pub sorting: Sorting,
-    player: MediaPlayer,
+    pub aliases: AliasTable,
+    pub cache_dir: String,
+    pub show_lyrics: bool,
+    pub lyrics_notice: Option<String>,
+    pub subtitle_arg: String,
+    pub karaoke: bool,
+    pub quality_policy: QualityPolicy,
+    pub trash_dir: String,
+    pub target_loudness: f64,
+    pub gain_arg: String,
+    pub image_protocol: ImageProtocol,
+    pub rename_template: String,
+    pub player_profiles: Vec<PlayerProfile>,
+    pub queue: PlayQueue,
+    pub queue_file: String,
+    player: Box<dyn PlayerBackend>,
+    now_playing: Option<String>,
+    player_events: UnboundedReceiver<PlayerEvent>,
 }
 
 impl AudioVideoData {
+    // The stores are shared with the views, which all run on the UI thread
+    #[allow(clippy::arc_with_non_send_sync)]
     pub fn new(
         data_file: &str,
         video_dir: String,
@@ -46,32 +118,754 @@ impl AudioVideoData {
         video_cmd: String,
         audio_cmd: String,
     ) -> Self {
+        let trash_dir = Path::new(&video_dir)
+            .join(".trash")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let (player_events_tx, player_events) = unbounded_channel();
+        let mut player = DualProcessPlayer::new(video_cmd, audio_cmd);
+        player.subscribe(player_events_tx);
         Self {
             data_file: data_file.to_string(),
             video_dir,
             audio_dir,
             audio_video,
+            meta_file: entry_meta::meta_file_for(data_file),
+            meta: Arc::new(RefCell::new(MetaStore::new())),
             video_list: None,
             search_filtered_list: None,
             sorting: Sorting::Descending,
-            player: MediaPlayer::new(video_cmd, audio_cmd),
-        }
-    }
-
-    pub async fn play_media(&mut self, video_name: &str) {
-        let prefix = Path::new(&self.video_dir);
-        let binding = prefix.join(video_name);
-        let video_path = binding.to_str().unwrap();
-        self.player
-            .play_media(
-                self.audio_video
-                    .borrow()
-                    .get(video_path)
-                    .unwrap()
-                    .to_owned(),
-                video_path.to_owned(),
-            )
-            .await;
+            aliases: AliasTable::default(),
+            cache_dir: entry_meta::cache_dir_for(data_file),
+            show_lyrics: false,
+            lyrics_notice: None,
+            subtitle_arg: "--sub-file=".to_string(),
+            karaoke: false,
+            quality_policy: QualityPolicy::default(),
+            trash_dir,
+            target_loudness: -18.0,
+            gain_arg: "--af=lavfi=[volume={gain}dB]".to_string(),
+            image_protocol: ImageProtocol::Disabled,
+            rename_template: renamer::DEFAULT_TEMPLATE.to_string(),
+            player_profiles: Vec::new(),
+            queue: PlayQueue::default(),
+            queue_file: play_queue::queue_file_for(data_file),
+            player: Box::new(player),
+            now_playing: None,
+            player_events,
+        }
+    }
+
+    pub async fn play_media(&mut self, video_name: &str) -> std::io::Result<()> {
+        self.play_media_version(video_name, None).await
+    }
+
+    /// Play the video with one of its audio versions, or with the linked audio when `version` is
+    /// None. In karaoke mode the instrumental of the linked audio is played instead if there is one
+    pub async fn play_media_version(
+        &mut self,
+        video_name: &str,
+        version: Option<&str>,
+    ) -> std::io::Result<()> {
+        let video_key = self.video_key(video_name);
+        let video_path = video_key.as_str();
+        let Some(linked_audio) = self.audio_video.borrow().get(video_path).cloned() else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} is not in the library", video_name),
+            ));
+        };
+        let meta = self.entry_meta(video_name);
+        let audio_path = match (version, &meta.instrumental) {
+            (Some(version), _) => version.to_owned(),
+            (None, Some(instrumental)) if self.karaoke && Path::new(instrumental).exists() => {
+                instrumental.to_owned()
+            }
+            _ => linked_audio.to_owned(),
+        };
+        let mut audio_args = Vec::new();
+        // The gain was measured on the linked audio
+        let gain = meta.gain_db.filter(|_| audio_path == linked_audio);
+        if let Some(gain) = gain {
+            audio_args.push(self.gain_arg.replace("{gain}", &format!("{:.2}", gain)));
+        }
+        let mut video_args = Vec::new();
+        self.lyrics_notice = None;
+        if self.show_lyrics {
+            let offset = meta.sync_offset_ms;
+            // Lyrics are looked up next to the linked audio, an instrumental has none
+            match lyrics::write_subtitle(&linked_audio, offset, &self.cache_dir, video_name) {
+                Ok(subtitle) => {
+                    video_args.push(format!("{}{}", self.subtitle_arg, subtitle.display()))
+                }
+                Err(notice) => self.lyrics_notice = Some(notice),
+            }
+        }
+        self.now_playing = Some(video_path.to_owned());
+        let parsed = self.parsed_name(video_name);
+        let profile = self.player_profile(video_name, &parsed, &meta);
+        self.player.play(PlayRequest {
+            audio_path,
+            video_path: video_path.to_owned(),
+            audio_args,
+            video_args,
+            sync_offset_ms: meta.sync_offset_ms,
+            start: meta.start,
+            end: meta.end,
+            volume: gain.map(|gain| 100.0 * 10f64.powf(gain / 20.0)),
+            title: parsed.title,
+            artist: parsed.artist,
+            video_cmd: profile.and_then(|p| p.video_cmd.clone()),
+            audio_cmd: profile.and_then(|p| p.audio_cmd.clone()),
+        });
+        Ok(())
+    }
+
+    /// Whether the video is linked to an audio file
+    pub fn has_video(&self, video_name: &str) -> bool {
+        self.audio_video
+            .borrow()
+            .contains_key(&self.video_key(video_name))
+    }
+
+    pub fn save_queue(&self) -> std::io::Result<()> {
+        self.queue.save(&self.queue_file)
+    }
+
+    /// The first player profile for the extension or categories of the video. The categories
+    /// are "live" or "mv" and the tags of the entry
+    fn player_profile(
+        &self,
+        video_name: &str,
+        parsed: &ParsedName,
+        meta: &EntryMeta,
+    ) -> Option<&PlayerProfile> {
+        let extension = Path::new(video_name)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        let mut categories = meta.tags.iter().cloned().collect::<Vec<_>>();
+        categories.push(
+            if parsed.version.is_some() {
+                "live"
+            } else {
+                "mv"
+            }
+            .to_owned(),
+        );
+        self.player_profiles
+            .iter()
+            .find(|profile| profile.matches(extension, &categories))
+    }
+
+    /// Replace the player. Events from the old player are no longer received
+    pub fn set_player(&mut self, mut player: Box<dyn PlayerBackend>) {
+        self.player.stop();
+        let (player_events_tx, player_events) = unbounded_channel();
+        player.subscribe(player_events_tx);
+        self.player = player;
+        self.player_events = player_events;
+    }
+
+    /// Wait for the player to finish or stop
+    pub async fn next_player_event(&mut self) -> Option<PlayerEvent> {
+        self.player_events.recv().await
+    }
+
+    /// Player events that arrived since they were last read
+    pub fn pending_player_events(&mut self) -> Vec<PlayerEvent> {
+        let mut events = Vec::new();
+        while let Ok(event) = self.player_events.try_recv() {
+            events.push(event);
+        }
+        events
+    }
+
+    pub fn stop_playback(&mut self) {
+        self.player.stop();
+    }
+
+    pub fn player_status(&self) -> PlayerStatus {
+        self.player.status()
+    }
+
+    pub fn control_player(&mut self, control: PlayerControl) -> std::io::Result<()> {
+        self.player.control(control)
+    }
+
+    pub fn playback_state(&mut self) -> Option<PlaybackState> {
+        self.player.playback_state()
+    }
+
+    /// All audio versions of the video with the linked audio, the default, first
+    pub fn audio_versions(&self, video_name: &str) -> Vec<AudioVersion> {
+        let linked_audio = self
+            .audio_video
+            .borrow()
+            .get(&self.video_key(video_name))
+            .cloned()
+            .unwrap_or_default();
+        self.entry_meta(video_name).versions(&linked_audio)
+    }
+
+    /// Make one of the alternate audio versions the linked audio of the video
+    pub fn set_default_version(&mut self, video_name: &str, path: &str) -> std::io::Result<()> {
+        let key = self.video_key(video_name);
+        let Some(linked_audio) = self.audio_video.borrow().get(&key).cloned() else {
+            return Ok(());
+        };
+        let mut changed = false;
+        self.update_meta(video_name, |m| {
+            changed = m.make_default(&linked_audio, path);
+            if changed {
+                m.gain_db = None;
+                m.bpm = None;
+                m.energy = None;
+            }
+        });
+        if changed {
+            self.audio_video.borrow_mut().insert(key, path.to_owned());
+            self.save_data()?;
+        }
+        Ok(())
+    }
+
+    /// The key used in `audio_video` and `meta` for a video name returned by `list_videos`
+    pub fn video_key(&self, video_name: &str) -> String {
+        Path::new(&self.video_dir)
+            .join(video_name)
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    /// The video name as returned by `list_videos` for a key in `audio_video`
+    pub fn video_name(&self, video_key: &str) -> String {
+        video_key
+            .trim_start_matches(&self.video_dir)
+            .trim_start_matches('/')
+            .trim_start_matches('\\')
+            .to_string()
+    }
+
+    /// The key used in `meta`. Normalized so that the meta is found whatever form the file
+    /// name has on disk
+    pub fn meta_key(&self, video_name: &str) -> String {
+        unicode_paths::nfc(&self.video_key(video_name))
+    }
+
+    pub fn entry_meta(&self, video_name: &str) -> EntryMeta {
+        self.meta
+            .borrow()
+            .get(&self.meta_key(video_name))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn update_meta<F: FnOnce(&mut EntryMeta)>(&mut self, video_name: &str, update: F) {
+        let key = self.meta_key(video_name);
+        update(self.meta.borrow_mut().entry(key).or_default());
+    }
+
+    pub fn parsed_name(&self, video_name: &str) -> ParsedName {
+        ParsedName::parse(video_name, &self.aliases)
+    }
+
+    /// Extra text for fzf to match on so that a video can be found by any alias of its artist,
+    /// and Korean names by their romanization or initial consonants
+    pub fn search_keys(&self, video_names: &[String]) -> HashMap<String, String> {
+        video_names
+            .iter()
+            .filter_map(|name| {
+                let parsed = self.parsed_name(name);
+                let mut texts = vec![format!("{} {}", parsed.raw_artist, parsed.title)];
+                let mut keys = Vec::new();
+                if self.aliases.resolve(&parsed.raw_artist).is_some() {
+                    let names = self.aliases.names_of(&parsed.artist);
+                    texts.extend(names.iter().cloned());
+                    keys.push(names.join(" "));
+                }
+                keys.extend(texts.iter().filter_map(|text| hangul::search_keys(text)));
+                if keys.is_empty() {
+                    return None;
+                }
+                Some((name.to_owned(), keys.join(" ")))
+            })
+            .collect()
+    }
+
+    /// The release date from the manual override, the file name prefix or the audio tags, in that
+    /// order
+    pub fn release_date(&self, video_name: &str) -> Option<ReleaseDate> {
+        let meta = self.entry_meta(video_name);
+        meta.release_date
+            .as_deref()
+            .and_then(ReleaseDate::parse)
+            .or_else(|| self.parsed_name(video_name).date)
+            .or_else(|| meta.tag_date.as_deref().and_then(ReleaseDate::parse))
+    }
+
+    /// Read the date tag of the linked audio for videos whose tags were not read yet. The dates
+    /// are kept in the meta so each file is only read once
+    pub fn read_tag_dates(&mut self, video_names: &[String]) {
+        let unchecked = video_names
+            .iter()
+            .filter(|name| !self.entry_meta(name).tag_date_checked)
+            .cloned()
+            .collect::<Vec<String>>();
+        if unchecked.is_empty() {
+            return;
+        }
+        println!("Reading release dates from {} files...", unchecked.len());
+        for name in unchecked.iter() {
+            let audio_path = self
+                .audio_video
+                .borrow()
+                .get(&self.video_key(name))
+                .cloned();
+            let tag_date = audio_path
+                .and_then(|path| probe::probe(&path))
+                .and_then(|info| {
+                    ["date", "originaldate", "release_date", "year"]
+                        .iter()
+                        .find_map(|tag| info.tag(tag).and_then(ReleaseDate::parse))
+                })
+                .map(|date| date.to_string());
+            self.update_meta(name, |m| {
+                m.tag_date = tag_date;
+                m.tag_date_checked = true;
+            });
+        }
+        // When this fails the tags are only read again next time
+        self.save_meta().ok();
+    }
+
+    /// Group videos by release year, or by artist and release year when `by_artist` is set.
+    /// Videos in a group are in release order and videos without a date are grouped as "Unknown"
+    pub fn group_by_era(
+        &mut self,
+        video_names: &[String],
+        by_artist: bool,
+    ) -> BTreeMap<String, Vec<String>> {
+        self.read_tag_dates(video_names);
+        let mut dated = video_names
+            .iter()
+            .map(|name| (self.release_date(name), name.to_owned()))
+            .collect::<Vec<(Option<ReleaseDate>, String)>>();
+        dated.sort();
+        let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (date, name) in dated {
+            let year = date
+                .map(|d| d.year.to_string())
+                .unwrap_or("Unknown".to_owned());
+            let key = if by_artist {
+                format!("{} / {}", self.parsed_name(&name).artist, year)
+            } else {
+                year
+            };
+            groups.entry(key).or_default().push(name);
+        }
+        groups
+    }
+
+    /// Videos released on today's date in earlier years, or in the current week if there are none
+    ///
+    /// Returns the videos ordered by year and whether the search was widened to the week
+    pub fn on_this_day(&mut self, video_names: &[String]) -> (Vec<String>, bool) {
+        self.read_tag_dates(video_names);
+        let dated = video_names
+            .iter()
+            .filter_map(|name| self.release_date(name).map(|date| (date, name.to_owned())))
+            .collect::<Vec<(ReleaseDate, String)>>();
+        release_date::anniversaries(&dated, ReleaseDate::today())
+    }
+
+    /// Look for the instrumental version of the linked audio of every entry in the audio directory
+    /// and store it in the meta
+    ///
+    /// Returns the number of entries that have an instrumental
+    pub fn detect_instrumentals(&mut self) -> std::io::Result<usize> {
+        println!("Scanning audio directory for instrumentals...");
+        let candidates =
+            instrumental::instrumental_candidates(&updater::scan_audio_files(&self.audio_dir));
+        let linked = self
+            .audio_video
+            .borrow()
+            .iter()
+            .map(|(video, audio)| (video.to_owned(), audio.to_owned()))
+            .collect::<Vec<(String, String)>>();
+        let mut found = 0;
+        for (video_path, audio_path) in linked {
+            let track = TrackInfo::read(&audio_path);
+            let inst = instrumental::find_instrumental(&track, &candidates);
+            found += usize::from(inst.is_some());
+            self.meta
+                .borrow_mut()
+                .entry(unicode_paths::nfc(&video_path))
+                .or_default()
+                .instrumental = inst;
+        }
+        self.save_meta()?;
+        Ok(found)
+    }
+
+    /// Find entries that are linked to lossy audio or that have a better copy of their audio
+    pub fn quality_report(&self) -> Vec<QualityEntry> {
+        println!("Scanning audio directory for better copies...");
+        let audio_files = updater::scan_audio_files(&self.audio_dir);
+        let mut linked = self
+            .audio_video
+            .borrow()
+            .iter()
+            .map(|(video, audio)| (self.video_name(video), audio.to_owned()))
+            .collect::<Vec<(String, String)>>();
+        linked.sort();
+        linked
+            .into_iter()
+            .map(|(video_name, audio_path)| QualityEntry {
+                lossy: !quality::is_lossless(&audio_path),
+                better_copy: quality::better_copy(&audio_path, &audio_files, &self.quality_policy),
+                video_name,
+                audio_path,
+            })
+            .filter(|entry| entry.lossy || entry.better_copy.is_some())
+            .collect()
+    }
+
+    /// The values for the rename template. Names without an artist take the artist and title from
+    /// the tags of the linked audio
+    fn name_fields(&self, video_name: &str) -> NameFields {
+        let parsed = self.parsed_name(video_name);
+        let mut fields = NameFields {
+            artist: parsed.artist,
+            title: parsed.title,
+            version: parsed.version,
+            date: self.release_date(video_name),
+        };
+        if parsed.raw_artist.is_empty() {
+            let tags = self
+                .audio_video
+                .borrow()
+                .get(&self.video_key(video_name))
+                .and_then(|audio| probe::probe(audio));
+            if let Some(tags) = tags {
+                if let (Some(artist), Some(title)) = (tags.tag("artist"), tags.tag("title")) {
+                    fields.artist = self.aliases.canonical(artist);
+                    fields.title = title.to_owned();
+                }
+            }
+        }
+        fields
+    }
+
+    /// The renames the template gives for the videos, split into the ones that can be done and
+    /// the conflicts
+    pub fn rename_plan(&mut self, video_names: &[String]) -> (Vec<Rename>, Vec<(Rename, String)>) {
+        self.read_tag_dates(video_names);
+        let renames = video_names
+            .iter()
+            .map(|video_name| {
+                let from = self.video_key(video_name);
+                let new_name =
+                    renamer::render(&self.rename_template, &self.name_fields(video_name));
+                let to = renamer::renamed_path(&from, &new_name);
+                Rename { from, to }
+            })
+            .collect();
+        renamer::plan(renames)
+    }
+
+    /// Rename the videos on disk and move their links and meta to the new names. If a rename or
+    /// saving the data file fails, the files that were already renamed are moved back. Once the
+    /// data file is saved the renames stay, even when the queue or meta can't be saved
+    ///
+    /// Returns the number of renamed videos
+    pub fn rename_videos(&mut self, renames: &[Rename]) -> Result<usize, RenameError> {
+        let mut done: Vec<&Rename> = Vec::new();
+        let undo = |done: &[&Rename]| {
+            for rename in done.iter().rev() {
+                fs::rename(&rename.to, &rename.from)
+                    .unwrap_or_else(|e| eprintln!("Couldn't move {} back: {}", rename.to, e));
+            }
+        };
+        for rename in renames {
+            if let Err(e) = fs::rename(&rename.from, &rename.to) {
+                undo(&done);
+                return Err(RenameError::NotRenamed(e));
+            }
+            done.push(rename);
+        }
+        let mut audio_video = self.audio_video.borrow().clone();
+        for rename in renames {
+            let from = unicode_paths::nfc(&rename.from);
+            let old_key = audio_video
+                .keys()
+                .find(|k| unicode_paths::nfc(k) == from)
+                .cloned();
+            if let Some(audio) = old_key.and_then(|k| audio_video.remove(&k)) {
+                audio_video.insert(rename.to.to_owned(), audio);
+            }
+        }
+        // The links are only replaced once the data file with the new names is in place
+        if let Err(e) = write_data(&self.data_file, &audio_video) {
+            undo(&done);
+            return Err(RenameError::NotRenamed(e));
+        }
+        *self.audio_video.borrow_mut() = audio_video;
+        for rename in renames {
+            self.video_moved(&rename.from, Some(&rename.to));
+        }
+        let saved = self.save_queue();
+        saved
+            .and(self.save_meta())
+            .map(|_| renames.len())
+            .map_err(|error| RenameError::NotSaved {
+                renamed: renames.len(),
+                error,
+            })
+    }
+
+    /// Bring everything else that holds the name of a video in line after it was renamed or
+    /// removed from the library: the meta, the thumbnail, the queue, the video lists and the
+    /// player. `to` is None for a removed video. A removed video is stopped if it plays, a renamed
+    /// one plays on from the open file
+    fn video_moved(&mut self, from: &str, to: Option<&str>) {
+        let key = unicode_paths::nfc(from);
+        let meta = self.meta.borrow_mut().remove(&key);
+        let from_name = self.video_name(from);
+        let thumbnail = thumbnails::thumbnail_path(&self.cache_dir, &from_name);
+        let playing = self
+            .now_playing
+            .as_ref()
+            .is_some_and(|path| unicode_paths::nfc(path) == key);
+        match to {
+            Some(to) => {
+                if let Some(meta) = meta {
+                    self.meta.borrow_mut().insert(unicode_paths::nfc(to), meta);
+                }
+                let to_name = self.video_name(to);
+                fs::rename(
+                    thumbnail,
+                    thumbnails::thumbnail_path(&self.cache_dir, &to_name),
+                )
+                .ok();
+                self.queue.rename(&from_name, &to_name);
+                if playing {
+                    self.now_playing = Some(to.to_owned());
+                }
+            }
+            None => {
+                fs::remove_file(thumbnail).ok();
+                self.queue.retain(|entry| entry != from_name);
+                if playing {
+                    self.stop_playback();
+                    self.now_playing = None;
+                }
+            }
+        }
+        self.video_list = None;
+        self.search_filtered_list = None;
+    }
+
+    /// Audit the links against the files on disk. The decode check reads every linked file
+    pub fn integrity_report(&self, decode_check: bool) -> IntegrityReport {
+        let other_audio = self
+            .meta
+            .borrow()
+            .values()
+            .flat_map(|meta| {
+                meta.audio_versions
+                    .iter()
+                    .map(|version| version.path.to_owned())
+                    .chain(meta.instrumental.clone())
+            })
+            .collect();
+        let audio_video = self.audio_video.borrow();
+        let mut report = IntegrityReport::new(
+            &self.video_dir,
+            &self.audio_dir,
+            &audio_video,
+            &other_audio,
+            &updater::scan_video_files(&self.video_dir),
+            &updater::scan_audio_files(&self.audio_dir),
+        );
+        if decode_check {
+            report.check_decodes(&audio_video);
+        }
+        report
+    }
+
+    /// Link the video to another audio file
+    pub fn relink(&mut self, video_name: &str, audio_path: &str) {
+        let key = self.video_key(video_name);
+        if self.audio_video.borrow().contains_key(&key) {
+            self.audio_video
+                .borrow_mut()
+                .insert(key, audio_path.to_owned());
+            // The analysis was done on the old audio
+            self.update_meta(video_name, |m| {
+                m.gain_db = None;
+                m.bpm = None;
+                m.energy = None;
+            });
+        }
+    }
+
+    fn is_hidden(&self, video_key: &str) -> bool {
+        self.meta
+            .borrow()
+            .get(&unicode_paths::nfc(video_key))
+            .is_some_and(|m| m.hidden)
+    }
+
+    /// Find groups of duplicate videos in the video directory, the highest resolution copy first
+    pub fn duplicate_groups(&self) -> Vec<Vec<VideoInfo>> {
+        println!("Scanning video directory for duplicates...");
+        let videos = updater::scan_video_files(&self.video_dir)
+            .iter()
+            .map(|path| VideoInfo::new(path, &self.parsed_name(&self.video_name(path))))
+            .collect();
+        duplicates::find_duplicates(videos, true)
+    }
+
+    /// Show the preferred copy and hide the others from lists and random
+    pub fn hide_duplicates(&mut self, keep: &str, others: &[String]) -> std::io::Result<()> {
+        let mut meta = self.meta.borrow_mut();
+        meta.entry(unicode_paths::nfc(keep)).or_default().hidden = false;
+        for other in others {
+            meta.entry(unicode_paths::nfc(other)).or_default().hidden = true;
+        }
+        drop(meta);
+        self.video_list = None;
+        self.save_data()
+    }
+
+    /// Move the videos to the trash directory and remove their entries. A file of the same name
+    /// that is already in the trash is kept, the video gets a numbered name instead. When a move
+    /// fails the videos moved before it stay moved and are saved as gone
+    ///
+    /// Returns the number of videos that were moved
+    pub fn trash_videos(&mut self, videos: &[String]) -> std::io::Result<usize> {
+        fs::create_dir_all(&self.trash_dir)?;
+        let mut moved = 0;
+        let mut result = Ok(());
+        for video in videos {
+            let Some(file_name) = Path::new(video).file_name() else {
+                continue;
+            };
+            let target = free_path(&Path::new(&self.trash_dir).join(file_name));
+            if let Err(e) = fs::rename(video, target) {
+                result = Err(e);
+                break;
+            }
+            let key = unicode_paths::nfc(video);
+            self.audio_video
+                .borrow_mut()
+                .retain(|k, _| unicode_paths::nfc(k) != key);
+            self.video_moved(video, None);
+            moved += 1;
+        }
+        let saved = self.save_data();
+        let queue_saved = self.save_queue();
+        result.and(saved).and(queue_saved).map(|_| moved)
+    }
+
+    /// Work out the gain of the linked audio for entries that do not have one yet
+    ///
+    /// Returns the number of entries that were analyzed
+    pub fn analyze_loudness(&mut self) -> std::io::Result<usize> {
+        let linked = self
+            .audio_video
+            .borrow()
+            .iter()
+            .filter(|(video, _)| self.entry_meta(&self.video_name(video)).gain_db.is_none())
+            .map(|(video, audio)| (self.video_name(video), audio.to_owned()))
+            .collect::<Vec<(String, String)>>();
+        let mut analyzed = 0;
+        for (i, (video_name, audio_path)) in linked.iter().enumerate() {
+            println!(
+                "Analyzing loudness {}/{}: {}",
+                i + 1,
+                linked.len(),
+                audio_path
+            );
+            if let Some(gain) = loudness::analyze(audio_path, self.target_loudness) {
+                self.update_meta(video_name, |m| m.gain_db = Some(gain));
+                analyzed += 1;
+            }
+        }
+        self.save_meta()?;
+        Ok(analyzed)
+    }
+
+    /// Work out the tempo and energy of the linked audio for entries that do not have them yet
+    ///
+    /// Returns the number of entries that were analyzed
+    pub fn analyze_tempo(&mut self) -> std::io::Result<usize> {
+        let linked = self
+            .audio_video
+            .borrow()
+            .iter()
+            .filter(|(video, _)| self.entry_meta(&self.video_name(video)).bpm.is_none())
+            .map(|(video, audio)| (self.video_name(video), audio.to_owned()))
+            .collect::<Vec<(String, String)>>();
+        let mut analyzed = 0;
+        for (i, (video_name, audio_path)) in linked.iter().enumerate() {
+            println!("Analyzing tempo {}/{}: {}", i + 1, linked.len(), audio_path);
+            if let Some(tempo) = tempo::analyze(audio_path) {
+                self.update_meta(video_name, |m| {
+                    m.bpm = Some(tempo.bpm);
+                    m.energy = Some(tempo.energy);
+                });
+                analyzed += 1;
+            }
+        }
+        if self.sorting == Sorting::Bpm {
+            self.video_list = None;
+        }
+        self.save_meta()?;
+        Ok(analyzed)
+    }
+
+    /// fzf preview command that shows the thumbnail of the selected video
+    pub fn preview_command(&self) -> Option<String> {
+        self.image_protocol.preview_command(&self.cache_dir)
+    }
+
+    /// Make thumbnails for the linked videos that do not have one yet in the background
+    pub fn generate_thumbnails(&self) -> Option<JoinHandle<usize>> {
+        if self.image_protocol == ImageProtocol::Disabled {
+            return None;
+        }
+        let videos = self
+            .audio_video
+            .borrow()
+            .keys()
+            .map(|video| {
+                let thumbnail =
+                    thumbnails::thumbnail_path(&self.cache_dir, &self.video_name(video));
+                (PathBuf::from(video), thumbnail)
+            })
+            .collect();
+        Some(thumbnails::generate_missing(videos))
+    }
+
+    /// Change the sorting, the video list is rebuilt on the next call to `list_videos`
+    pub fn set_sorting(&mut self, sorting: Sorting) {
+        if self.sorting != sorting {
+            self.sorting = sorting;
+            self.video_list = None;
+        }
+    }
+
+    /// All tags that are used by at least one entry
+    pub fn all_tags(&self) -> BTreeSet<String> {
+        self.meta
+            .borrow()
+            .values()
+            .flat_map(|m| m.tags.iter().cloned())
+            .collect()
     }
 
     pub fn load_data(&mut self) {
@@ -79,28 +873,51 @@ impl AudioVideoData {
         let read_data =
             serde_json::from_str::<JsonFormat>(&data).expect("Unable to parse data file");
         let mut update_save = false;
+        // Paths may be stored in a different normalization form than the one on disk
+        let mut video_index = unicode_paths::DiskIndex::new(&self.video_dir, false);
+        let mut audio_index = unicode_paths::DiskIndex::new(&self.audio_dir, true);
         for (video_path, audio_path) in read_data {
             let full_vpath = Path::new(&self.video_dir).join(&video_path);
             let full_apath = Path::new(&self.audio_dir).join(&audio_path);
-            if !full_vpath.exists() || !full_apath.exists() {
+            let full_vpath = full_vpath.to_str().unwrap();
+            let full_apath = full_apath.to_str().unwrap();
+            let (Some(disk_vpath), Some(disk_apath)) = (
+                video_index.resolve(full_vpath),
+                audio_index.resolve(full_apath),
+            ) else {
                 update_save = true;
                 continue;
+            };
+            if disk_vpath != full_vpath || disk_apath != full_apath {
+                update_save = true;
             }
-            self.audio_video.borrow_mut().insert(
-                full_vpath.to_str().unwrap().to_string(),
-                full_apath.to_str().unwrap().to_string(),
-            );
+            self.audio_video.borrow_mut().insert(disk_vpath, disk_apath);
         }
+        *self.meta.borrow_mut() = entry_meta::load_meta(&self.meta_file)
+            .into_iter()
+            .map(|(key, meta)| (unicode_paths::nfc(&key), meta))
+            .collect();
         if update_save {
-            self.save_data();
+            self.save_data().expect("Unable to write data file");
+        }
+        // Entries of videos that are gone from the library can't be played
+        let mut queue = PlayQueue::load(&self.queue_file);
+        let queued = queue.entries.len();
+        queue.retain(|entry| self.has_video(entry));
+        self.queue = queue;
+        if self.queue.entries.len() != queued {
+            // The entries are dropped again on the next load when this fails
+            self.save_queue().ok();
         }
     }
 
-    pub fn save_data(&mut self) {
-        let to_save = self.audio_video.borrow().clone();
-        let data = serde_json::to_string_pretty(&to_save).unwrap();
-        let mut file = fs::File::create(&self.data_file).unwrap();
-        file.write_all(data.as_bytes()).unwrap();
+    pub fn save_data(&mut self) -> std::io::Result<()> {
+        write_data(&self.data_file, &self.audio_video.borrow())?;
+        self.save_meta()
+    }
+
+    pub fn save_meta(&self) -> std::io::Result<()> {
+        entry_meta::save_meta(&self.meta_file, &self.meta.borrow())
     }
 
     pub fn list_videos(&mut self) -> Vec<String> {
@@ -115,17 +932,16 @@ impl AudioVideoData {
             .borrow()
             .clone()
             .keys()
-            .map(|k| {
-                k.to_string()
-                    .trim_start_matches(&self.video_dir)
-                    .trim_start_matches('/')
-                    .trim_start_matches('\\')
-                    .to_string()
-            })
+            .filter(|k| !self.is_hidden(k))
+            .map(|k| self.video_name(k))
             .collect::<Vec<String>>();
         match self.sorting {
-            Sorting::Ascending => vlist.sort(),
-            Sorting::Descending => vlist.sort_by(|a, b| b.cmp(a)),
+            Sorting::Ascending => {
+                vlist.sort_by_cached_key(|k| (self.parsed_name(k).artist, k.to_owned()))
+            }
+            Sorting::Descending => vlist.sort_by_cached_key(|k| {
+                std::cmp::Reverse((self.parsed_name(k).artist, k.to_owned()))
+            }),
             Sorting::Mtime => {
                 let mut vlist2 = vlist
                     .iter()
@@ -136,18 +952,70 @@ impl AudioVideoData {
                         (k.to_string(), mtime)
                     })
                     .collect::<Vec<(String, std::time::SystemTime)>>();
-                vlist2.sort_by(|a, b| b.1.cmp(&a.1));
+                vlist2.sort_by_key(|k| std::cmp::Reverse(k.1));
                 vlist = vlist2.iter().map(|k| k.0.to_string()).collect();
             }
+            Sorting::ReleaseDate => {
+                self.read_tag_dates(&vlist);
+                // Oldest first, videos without a date at the end
+                vlist.sort_by_cached_key(|k| {
+                    let date = self.release_date(k);
+                    (date.is_none(), date, k.to_owned())
+                });
+            }
+            Sorting::Bpm => {
+                // Slowest first, videos that were not analyzed at the end
+                let mut vlist2 = vlist
+                    .iter()
+                    .map(|k| (self.entry_meta(k).bpm, k.to_owned()))
+                    .collect::<Vec<(Option<f64>, String)>>();
+                vlist2.sort_by(|a, b| match (a.0, b.0) {
+                    (Some(x), Some(y)) => x.total_cmp(&y).then_with(|| a.1.cmp(&b.1)),
+                    (x, y) => x.is_none().cmp(&y.is_none()).then_with(|| a.1.cmp(&b.1)),
+                });
+                vlist = vlist2.into_iter().map(|k| k.1).collect();
+            }
         }
         self.video_list = Some(vlist.clone());
         vlist
     }
 }
 
+/// The path, or the path with a number added to the file stem when it is taken, for example
+/// "IU - Blueming (1).mp4"
+fn free_path(path: &Path) -> PathBuf {
+    if !path.exists() {
+        return path.to_owned();
+    }
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
+
+/// Write the data file through a temporary file so it is never left half written
+fn write_data(data_file: &str, audio_video: &HashMap<String, String>) -> std::io::Result<()> {
+    let data = serde_json::to_string_pretty(audio_video).unwrap();
+    let temp_file = format!("{}.tmp", data_file);
+    let mut file = fs::File::create(&temp_file)?;
+    file.write_all(data.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temp_file, data_file)
+}
+
 #[cfg(test)]
+#[allow(clippy::arc_with_non_send_sync)]
 mod tests {
     use super::*;
+    use crate::media_player::RecordingPlayer;
     use tempdir::TempDir;
 
     #[test]
@@ -175,7 +1043,7 @@ mod tests {
             .as_ref()
             .borrow_mut()
             .insert("video/2.mp4".to_string(), "audio/2.mp3".to_string());
-        av_data.save_data();
+        av_data.save_data().unwrap();
         let data = fs::read_to_string(&data_file).unwrap();
         let read_data = serde_json::from_str::<JsonFormat>(&data).unwrap();
         assert_eq!(read_data.len(), 2);
@@ -218,7 +1086,7 @@ mod tests {
             video_file1.to_str().unwrap().to_owned(),
             audio_file1.to_str().unwrap().to_owned(),
         );
-        av_data.save_data();
+        av_data.save_data().unwrap();
         av_data.audio_video.as_ref().borrow_mut().clear();
         av_data.load_data();
         assert_eq!(av_data.audio_video.borrow().len(), 1);
@@ -251,9 +1119,351 @@ mod tests {
             video_file1.to_str().unwrap().to_owned(),
             audio_file1.to_str().unwrap().to_owned(),
         );
-        av_data.save_data();
+        av_data.save_data().unwrap();
         av_data.audio_video.as_ref().borrow_mut().clear();
         av_data.load_data();
         assert_eq!(av_data.audio_video.borrow().len(), 0);
     }
+
+    #[test]
+    fn test_list_videos_groups_aliases() {
+        let rc = Arc::new(RefCell::new(HashMap::new()));
+        let mut av_data = AudioVideoData::new(
+            "data.json",
+            "video".to_string(),
+            "audio".to_string(),
+            rc,
+            "".to_string(),
+            "".to_string(),
+        );
+        let mut aliases = HashMap::new();
+        aliases.insert("IU".to_string(), vec!["아이유".to_string()]);
+        av_data.aliases = AliasTable::new(&aliases);
+        for name in [
+            "아이유 - Blueming.mp4",
+            "Jay Park - Mommae.mp4",
+            "IU - Palette.mp4",
+        ] {
+            av_data
+                .audio_video
+                .borrow_mut()
+                .insert(format!("video/{}", name), "audio/1.mp3".to_string());
+        }
+        av_data.sorting = Sorting::Ascending;
+        assert_eq!(
+            av_data.list_videos(),
+            vec![
+                "IU - Palette.mp4".to_string(),
+                "아이유 - Blueming.mp4".to_string(),
+                "Jay Park - Mommae.mp4".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_data_nfd_on_disk() {
+        let temp_dir = TempDir::new("test_load_data_nfd").unwrap();
+        let data_file = temp_dir.path().join("data.json");
+        let video_dir = temp_dir.path().join("video");
+        let audio_dir = temp_dir.path().join("audio");
+        let nfd_video = video_dir.join("\u{110b}\u{1161}\u{110b}\u{1175}\u{110b}\u{1172}.mp4");
+        let nfc_video = video_dir.join("\u{c544}\u{c774}\u{c720}.mp4");
+        let audio_file1 = audio_dir.join("1.mp3");
+        create_file(&video_dir, &nfd_video).unwrap();
+        create_file(&audio_dir, &audio_file1).unwrap();
+        let mut saved = HashMap::new();
+        saved.insert(
+            nfc_video.to_str().unwrap().to_owned(),
+            audio_file1.to_str().unwrap().to_owned(),
+        );
+        fs::write(&data_file, serde_json::to_string(&saved).unwrap()).unwrap();
+        let rc = Arc::new(RefCell::new(HashMap::new()));
+        let mut av_data = AudioVideoData::new(
+            data_file.to_str().unwrap(),
+            video_dir.to_str().unwrap().to_string(),
+            audio_dir.to_str().unwrap().to_string(),
+            rc,
+            "".to_string(),
+            "".to_string(),
+        );
+        av_data.load_data();
+        assert!(av_data
+            .audio_video
+            .borrow()
+            .contains_key(nfd_video.to_str().unwrap()));
+    }
+
+    #[test]
+    fn test_hidden_duplicates_not_listed() {
+        let temp_dir = TempDir::new("test_hidden_duplicates").unwrap();
+        let data_file = temp_dir.path().join("data.json");
+        let rc = Arc::new(RefCell::new(HashMap::new()));
+        let mut av_data = AudioVideoData::new(
+            data_file.to_str().unwrap(),
+            "video".to_string(),
+            "audio".to_string(),
+            rc,
+            "".to_string(),
+            "".to_string(),
+        );
+        for name in ["video/IU - Blueming.mp4", "video/IU - Blueming [4K].mp4"] {
+            av_data
+                .audio_video
+                .borrow_mut()
+                .insert(name.to_string(), "audio/1.mp3".to_string());
+        }
+        av_data
+            .hide_duplicates(
+                "video/IU - Blueming [4K].mp4",
+                &["video/IU - Blueming.mp4".to_string()],
+            )
+            .unwrap();
+        assert_eq!(
+            av_data.list_videos(),
+            vec!["IU - Blueming [4K].mp4".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_rename_videos() {
+        let temp_dir = TempDir::new("test_rename_videos").unwrap();
+        let data_file = temp_dir.path().join("data.json");
+        let video_dir = temp_dir.path().join("video");
+        let old_video = video_dir.join("191118 IU - Blueming(4K).mp4");
+        let new_video = video_dir.join("IU - Blueming (4K) [2019-11-18].mp4");
+        create_file(&video_dir, &old_video).unwrap();
+        let rc = Arc::new(RefCell::new(HashMap::new()));
+        let mut av_data = AudioVideoData::new(
+            data_file.to_str().unwrap(),
+            video_dir.to_str().unwrap().to_string(),
+            "audio".to_string(),
+            rc,
+            "".to_string(),
+            "".to_string(),
+        );
+        av_data.audio_video.borrow_mut().insert(
+            old_video.to_str().unwrap().to_string(),
+            "audio/1.mp3".to_string(),
+        );
+        av_data.update_meta("191118 IU - Blueming(4K).mp4", |m| m.favourite = true);
+        av_data.queue.add("191118 IU - Blueming(4K).mp4");
+        let (renames, conflicts) =
+            av_data.rename_plan(&["191118 IU - Blueming(4K).mp4".to_string()]);
+        assert!(conflicts.is_empty());
+        assert_eq!(renames[0].to, new_video.to_str().unwrap());
+        assert_eq!(av_data.rename_videos(&renames).unwrap(), 1);
+        assert!(!old_video.exists() && new_video.exists());
+        let saved: HashMap<String, String> =
+            serde_json::from_str(&fs::read_to_string(&data_file).unwrap()).unwrap();
+        assert_eq!(
+            saved.get(new_video.to_str().unwrap()).unwrap(),
+            "audio/1.mp3"
+        );
+        assert!(
+            av_data
+                .entry_meta("IU - Blueming (4K) [2019-11-18].mp4")
+                .favourite
+        );
+        assert_eq!(
+            av_data.queue.entries,
+            vec!["IU - Blueming (4K) [2019-11-18].mp4"]
+        );
+        // The renamed file already follows the template
+        let (renames, conflicts) =
+            av_data.rename_plan(&["IU - Blueming (4K) [2019-11-18].mp4".to_string()]);
+        assert!(renames.is_empty() && conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_rename_videos_reports_unsaved() {
+        let temp_dir = TempDir::new("test_rename_videos_reports_unsaved").unwrap();
+        let data_file = temp_dir.path().join("data.json");
+        let video_dir = temp_dir.path().join("video");
+        let old_video = video_dir.join("IU - Blueming(4K).mp4");
+        let new_video = video_dir.join("IU - Blueming (4K).mp4");
+        create_file(&video_dir, &old_video).unwrap();
+        let rc = Arc::new(RefCell::new(HashMap::new()));
+        let mut av_data = AudioVideoData::new(
+            data_file.to_str().unwrap(),
+            video_dir.to_str().unwrap().to_string(),
+            "audio".to_string(),
+            rc,
+            "".to_string(),
+            "".to_string(),
+        );
+        av_data.audio_video.borrow_mut().insert(
+            old_video.to_str().unwrap().to_string(),
+            "audio/1.mp3".to_string(),
+        );
+        // The temporary queue file can't be created
+        fs::create_dir(format!("{}.tmp", av_data.queue_file)).unwrap();
+        let renames = [Rename {
+            from: old_video.to_str().unwrap().to_string(),
+            to: new_video.to_str().unwrap().to_string(),
+        }];
+        match av_data.rename_videos(&renames) {
+            Err(RenameError::NotSaved { renamed: 1, .. }) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(!old_video.exists() && new_video.exists());
+    }
+
+    #[tokio::test]
+    async fn test_trash_videos_keeps_existing() {
+        let temp_dir = TempDir::new("test_trash_videos").unwrap();
+        let data_file = temp_dir.path().join("data.json");
+        let video_dir = temp_dir.path().join("video");
+        let video = video_dir.join("IU - Blueming.mp4");
+        create_file(&video_dir, &video).unwrap();
+        let rc = Arc::new(RefCell::new(HashMap::new()));
+        let mut av_data = AudioVideoData::new(
+            data_file.to_str().unwrap(),
+            video_dir.to_str().unwrap().to_string(),
+            "audio".to_string(),
+            rc,
+            "".to_string(),
+            "".to_string(),
+        );
+        let trash_dir = temp_dir.path().join("trash");
+        av_data.trash_dir = trash_dir.to_str().unwrap().to_string();
+        create_file(&trash_dir, &trash_dir.join("IU - Blueming.mp4")).unwrap();
+        av_data.audio_video.borrow_mut().insert(
+            video.to_str().unwrap().to_string(),
+            "audio/1.mp3".to_string(),
+        );
+        av_data.set_player(Box::<RecordingPlayer>::default());
+        av_data.play_media("IU - Blueming.mp4").await.unwrap();
+        av_data.queue.add("IU - Blueming.mp4");
+        let missing = video_dir.join("missing.mp4").to_str().unwrap().to_string();
+        let videos = [video.to_str().unwrap().to_string(), missing];
+        assert!(av_data.trash_videos(&videos).is_err());
+        assert_eq!(av_data.player_status(), PlayerStatus::Idle);
+        assert!(av_data.queue.entries.is_empty());
+        assert!(trash_dir.join("IU - Blueming.mp4").exists());
+        assert!(trash_dir.join("IU - Blueming (1).mp4").exists());
+        // The video that was moved before the failure is saved as gone
+        let saved: HashMap<String, String> =
+            serde_json::from_str(&fs::read_to_string(&data_file).unwrap()).unwrap();
+        assert!(saved.is_empty());
+    }
+
+    /// A library with one linked video that plays into a recording player
+    fn recording_library(temp_dir: &TempDir) -> (AudioVideoData, RecordingPlayer) {
+        let data_file = temp_dir.path().join("data.json");
+        let rc = Arc::new(RefCell::new(HashMap::new()));
+        let mut av_data = AudioVideoData::new(
+            data_file.to_str().unwrap(),
+            "video".to_string(),
+            "audio".to_string(),
+            rc,
+            "".to_string(),
+            "".to_string(),
+        );
+        let player = RecordingPlayer::default();
+        av_data.set_player(Box::new(player.clone()));
+        av_data.audio_video.borrow_mut().insert(
+            "video/IU - Blueming.mp4".to_string(),
+            "audio/1.mp3".to_string(),
+        );
+        (av_data, player)
+    }
+
+    #[tokio::test]
+    async fn test_play_media_applies_gain() {
+        let temp_dir = TempDir::new("test_play_media_applies_gain").unwrap();
+        let (mut av_data, player) = recording_library(&temp_dir);
+        av_data.update_meta("IU - Blueming.mp4", |m| m.gain_db = Some(-3.5));
+        av_data.play_media("IU - Blueming.mp4").await.unwrap();
+        let request = player.requests.lock().unwrap()[0].clone();
+        assert_eq!(request.audio_args, vec!["--af=lavfi=[volume=-3.50dB]"]);
+        assert_eq!(request.volume, Some(100.0 * 10f64.powf(-3.5 / 20.0)));
+        // The gain was measured on the linked audio, not on another version
+        av_data
+            .play_media_version("IU - Blueming.mp4", Some("audio/1 (Inst).mp3"))
+            .await
+            .unwrap();
+        let request = player.requests.lock().unwrap()[1].clone();
+        assert!(request.audio_args.is_empty());
+        assert_eq!(request.volume, None);
+    }
+
+    #[tokio::test]
+    async fn test_play_media_sends_request_to_backend() {
+        let temp_dir = TempDir::new("test_play_media_sends_request").unwrap();
+        let (mut av_data, player) = recording_library(&temp_dir);
+        av_data.play_media("IU - Blueming.mp4").await.unwrap();
+        assert_eq!(
+            player.requests.lock().unwrap()[0],
+            PlayRequest {
+                audio_path: "audio/1.mp3".to_string(),
+                video_path: "video/IU - Blueming.mp4".to_string(),
+                title: "Blueming".to_string(),
+                artist: "IU".to_string(),
+                ..Default::default()
+            }
+        );
+        assert_eq!(av_data.player_status(), PlayerStatus::Playing);
+        av_data.stop_playback();
+        assert_eq!(av_data.player_status(), PlayerStatus::Idle);
+        assert!(av_data.play_media("missing.mp4").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_player_events() {
+        let temp_dir = TempDir::new("test_player_events").unwrap();
+        let (mut av_data, player) = recording_library(&temp_dir);
+        av_data.play_media("IU - Blueming.mp4").await.unwrap();
+        player.finish();
+        assert_eq!(
+            av_data.next_player_event().await,
+            Some(PlayerEvent::Finished("video/IU - Blueming.mp4".to_string()))
+        );
+        av_data.play_media("IU - Blueming.mp4").await.unwrap();
+        av_data.stop_playback();
+        assert_eq!(
+            av_data.pending_player_events(),
+            vec![PlayerEvent::Stopped("video/IU - Blueming.mp4".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_play_media_uses_profile() {
+        let temp_dir = TempDir::new("test_play_media_uses_profile").unwrap();
+        let (mut av_data, player) = recording_library(&temp_dir);
+        av_data.player_profiles = vec![
+            PlayerProfile {
+                extensions: vec!["ts".to_string()],
+                video_cmd: Some("mpv --vf=yadif {video}".to_string()),
+                ..Default::default()
+            },
+            PlayerProfile {
+                categories: vec!["mv".to_string()],
+                video_cmd: Some("mpv --title={title} {video}".to_string()),
+                ..Default::default()
+            },
+        ];
+        av_data.play_media("IU - Blueming.mp4").await.unwrap();
+        let request = player.requests.lock().unwrap()[0].clone();
+        assert_eq!(
+            request.video_cmd,
+            Some("mpv --title={title} {video}".to_string())
+        );
+        assert_eq!(request.audio_cmd, None);
+    }
+
+    #[test]
+    fn test_relink_clears_analysis() {
+        let temp_dir = TempDir::new("test_relink_clears_analysis").unwrap();
+        let (mut av_data, _) = recording_library(&temp_dir);
+        av_data.update_meta("IU - Blueming.mp4", |m| {
+            m.gain_db = Some(-3.5);
+            m.bpm = Some(120.0);
+            m.energy = Some(0.5);
+            m.favourite = true;
+        });
+        av_data.relink("IU - Blueming.mp4", "audio/1.flac");
+        let meta = av_data.entry_meta("IU - Blueming.mp4");
+        assert_eq!((meta.gain_db, meta.bpm, meta.energy), (None, None, None));
+        assert!(meta.favourite);
+    }
 }
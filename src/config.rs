@@ -1,4 +1,8 @@
+use super::player_command::PlayerProfile;
+use super::quality::{self, QualityPolicy};
+use super::renamer;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::fs;
 
@@ -9,6 +13,78 @@ pub struct Config {
     pub audio_dir: String,
     pub video_cmd: String,
     pub audio_cmd: String,
+    /// Canonical artist name to the name variants that should be treated as that artist
+    #[serde(default)]
+    pub artist_aliases: HashMap<String, Vec<String>>,
+    /// Argument the subtitle file path is appended to when passing lyrics to the video player
+    #[serde(default = "default_subtitle_arg")]
+    pub subtitle_arg: String,
+    /// Audio file extensions from most to least preferred when a track exists in several formats
+    #[serde(default = "quality::default_format_preference")]
+    pub format_preference: Vec<String>,
+    /// Prefer the copy with the higher bit rate over the format preference
+    #[serde(default)]
+    pub prefer_higher_bitrate: bool,
+    /// Where duplicate MVs are moved to. Defaults to `.trash` inside the video directory
+    #[serde(default)]
+    pub trash_dir: Option<String>,
+    /// Loudness in LUFS that playback is levelled to
+    #[serde(default = "default_target_loudness")]
+    pub target_loudness: f64,
+    /// Audio player argument that applies a gain, `{gain}` is replaced by the gain in dB
+    #[serde(default = "default_gain_arg")]
+    pub gain_arg: String,
+    /// How thumbnails are shown in the preview pane: kitty, sixel, chafa or none. Detected from
+    /// the terminal when not set
+    #[serde(default)]
+    pub preview_protocol: Option<String>,
+    /// Template for canonical video file names, see `renamer::render`
+    #[serde(default = "default_rename_template")]
+    pub rename_template: String,
+    /// How media is played: "dual" runs `video_cmd` and `audio_cmd` as two processes, "synced"
+    /// does the same with two mpv that start together and are kept in sync, "mpv" runs a single
+    /// `video_cmd` mpv with the audio as an external track, "record" only records what would be
+    /// played
+    #[serde(default = "default_player_backend")]
+    pub player_backend: String,
+    /// Drift between the synced players that is corrected, in milliseconds
+    #[serde(default = "default_drift_threshold_ms")]
+    pub drift_threshold_ms: u64,
+    /// Commands used instead of `video_cmd` and `audio_cmd` for some videos. The first profile
+    /// that matches is used
+    #[serde(default)]
+    pub player_profiles: Vec<PlayerProfile>,
+    /// Video player commands tried in order when `video_cmd` can't be started or fails right
+    /// away, for example vlc then ffplay. They only get the file, or the placeholders of a template
+    #[serde(default)]
+    pub fallback_video_cmds: Vec<String>,
+    /// Audio player commands tried in order when `audio_cmd` can't be started or fails right away
+    #[serde(default)]
+    pub fallback_audio_cmds: Vec<String>,
+}
+
+fn default_drift_threshold_ms() -> u64 {
+    150
+}
+
+fn default_player_backend() -> String {
+    "dual".to_owned()
+}
+
+fn default_rename_template() -> String {
+    renamer::DEFAULT_TEMPLATE.to_owned()
+}
+
+fn default_target_loudness() -> f64 {
+    -18.0
+}
+
+fn default_gain_arg() -> String {
+    "--af=lavfi=[volume={gain}dB]".to_owned()
+}
+
+fn default_subtitle_arg() -> String {
+    "--sub-file=".to_owned()
 }
 
 #[derive(Debug)]
@@ -48,6 +124,13 @@ impl std::error::Error for ConfigError {
 }
 
 impl Config {
+    pub fn quality_policy(&self) -> QualityPolicy {
+        QualityPolicy {
+            format_preference: self.format_preference.clone(),
+            prefer_higher_bitrate: self.prefer_higher_bitrate,
+        }
+    }
+
     pub fn build(file_name: &str) -> Result<Config, ConfigError> {
         let file_data = fs::read_to_string(file_name)?;
         let yaml: Config = serde_yaml::from_str(file_data.as_str())?;
@@ -0,0 +1,149 @@
+use super::mv_name::ParsedName;
+use super::probe;
+use std::collections::BTreeMap;
+
+/// Words that describe the upload rather than the MV, ignored when comparing names
+const NOISE_WORDS: [&str; 14] = [
+    "official",
+    "music",
+    "video",
+    "mv",
+    "m/v",
+    "4k",
+    "2160p",
+    "1440p",
+    "1080p",
+    "720p",
+    "hd",
+    "uhd",
+    "60fps",
+    "remastered",
+];
+
+/// Longest difference in seconds between two copies of the same MV
+const MAX_DURATION_DIFF: f64 = 3.0;
+
+fn name_key(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| c.is_whitespace() || "()[]-_".contains(c))
+        .filter(|word| !word.is_empty() && !NOISE_WORDS.contains(word))
+        .collect::<String>()
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+/// A video file and what is known about it to find duplicates
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoInfo {
+    pub path: String,
+    pub key: String,
+    pub duration: Option<f64>,
+    pub width: Option<u64>,
+    pub height: Option<u64>,
+}
+
+impl VideoInfo {
+    /// Build the info from the parsed name. Duration and resolution are filled in by `probe`
+    pub fn new(path: &str, parsed: &ParsedName) -> Self {
+        Self {
+            path: path.to_owned(),
+            key: format!(
+                "{}|{}",
+                name_key(&parsed.artist),
+                name_key(&format!(
+                    "{} {}",
+                    parsed.title,
+                    parsed.version.as_deref().unwrap_or_default()
+                ))
+            ),
+            duration: None,
+            width: None,
+            height: None,
+        }
+    }
+
+    pub fn probe(&mut self) {
+        if let Some(info) = probe::probe(&self.path) {
+            self.duration = info.duration;
+            self.width = info.width;
+            self.height = info.height;
+        }
+    }
+
+    pub fn resolution(&self) -> String {
+        match (self.width, self.height) {
+            (Some(w), Some(h)) => format!("{}x{}", w, h),
+            _ => "unknown".to_owned(),
+        }
+    }
+
+    fn same_duration(&self, other: &VideoInfo) -> bool {
+        match (self.duration, other.duration) {
+            (Some(a), Some(b)) => (a - b).abs() <= MAX_DURATION_DIFF,
+            _ => true,
+        }
+    }
+}
+
+/// Group videos that share the parsed artist, title and version. Only groups with more than one
+/// video are probed, and groups are split further when the durations differ
+///
+/// Returns groups of duplicates, the highest resolution copy first
+pub fn find_duplicates(videos: Vec<VideoInfo>, probe_files: bool) -> Vec<Vec<VideoInfo>> {
+    let mut by_key: BTreeMap<String, Vec<VideoInfo>> = BTreeMap::new();
+    for video in videos {
+        by_key.entry(video.key.to_owned()).or_default().push(video);
+    }
+    let mut groups = Vec::new();
+    for (_, mut candidates) in by_key.into_iter().filter(|(_, v)| v.len() > 1) {
+        if probe_files {
+            candidates.iter_mut().for_each(|video| video.probe());
+        }
+        let mut split: Vec<Vec<VideoInfo>> = Vec::new();
+        for video in candidates {
+            match split
+                .iter_mut()
+                .find(|group| group.iter().all(|other| other.same_duration(&video)))
+            {
+                Some(group) => group.push(video),
+                None => split.push(vec![video]),
+            }
+        }
+        for mut group in split.into_iter().filter(|g| g.len() > 1) {
+            group.sort_by_key(|v| std::cmp::Reverse((v.height, v.width)));
+            groups.push(group);
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mv_name::AliasTable;
+
+    fn video(name: &str, duration: f64, height: u64) -> VideoInfo {
+        let mut info = VideoInfo::new(name, &ParsedName::parse(name, &AliasTable::default()));
+        info.duration = Some(duration);
+        info.height = Some(height);
+        info.width = Some(height * 16 / 9);
+        info
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let videos = vec![
+            video("IU - Blueming (Official MV).mp4", 215.0, 1080),
+            video("IU - Blueming [4K].webm", 216.5, 2160),
+            video("IU - Blueming (Live).mp4", 240.0, 1080),
+            video("IU - Blueming (Live) (1080p).mkv", 300.0, 720),
+            video("IU - Palette.mp4", 200.0, 1080),
+        ];
+        let groups = find_duplicates(videos, false);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].len(), 2);
+        assert_eq!(groups[0][0].path, "IU - Blueming [4K].webm");
+        assert_eq!(groups[0][0].resolution(), "3840x2160");
+    }
+}
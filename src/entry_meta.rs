@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Per entry data that is stored next to the data file. Keyed by the NFC form of the full video
+/// path that is used as the key in the audio_video data
+pub type MetaStore = HashMap<String, EntryMeta>;
+
+/// Used to store the extra data for an entry
+/// # Fields
+/// * `tags`: Free text tags, for example "choreo" or "broken-sync"
+/// * `rating`: Star rating from 1 to 5
+/// * `favourite`: Whether the entry is marked as a favourite
+/// * `release_date`: Release date that was entered by hand, overrides any other release date
+/// * `tag_date`: Release date read from the tags of the linked audio
+/// * `tag_date_checked`: Whether the tags were read, so files without a date are not read again
+/// * `sync_offset_ms`: How much later the audio should play than the video, in milliseconds
+/// * `instrumental`: Path of the instrumental version of the linked audio
+/// * `audio_label`: Label of the linked audio, the default version
+/// * `audio_versions`: Other audio versions that can be played with the video
+/// * `hidden`: Hidden from lists and random, for example a duplicate of a preferred copy
+/// * `gain_db`: Gain that brings the linked audio to the target loudness
+/// * `bpm`: Estimated tempo of the linked audio
+/// * `energy`: Overall energy of the linked audio from 0 to 100
+/// * `start`, `end`: Part of the video to play in seconds, for players that take them through
+///   `{start}` and `{end}` in a command template
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EntryMeta {
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<u8>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub favourite: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag_date: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub tag_date_checked: bool,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub sync_offset_ms: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instrumental: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_label: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub audio_versions: Vec<AudioVersion>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hidden: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gain_db: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bpm: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub energy: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<f64>,
+}
+
+/// An alternate audio version of an entry, for example a remaster or a live recording
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AudioVersion {
+    pub label: String,
+    pub path: String,
+}
+
+/// Label of the linked audio when it was not given one
+pub const DEFAULT_AUDIO_LABEL: &str = "Original";
+
+fn is_zero(value: &i64) -> bool {
+    *value == 0
+}
+
+pub const MAX_RATING: u8 = 5;
+
+impl EntryMeta {
+    pub fn is_empty(&self) -> bool {
+        *self == EntryMeta::default()
+    }
+
+    /// Set the rating. Values outside of 1..=5 clear the rating
+    pub fn set_rating(&mut self, rating: Option<u8>) {
+        self.rating = rating.filter(|r| (1..=MAX_RATING).contains(r));
+    }
+
+    pub fn toggle_tag(&mut self, tag: &str) {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return;
+        }
+        if !self.tags.remove(tag) {
+            self.tags.insert(tag.to_owned());
+        }
+    }
+
+    /// All audio versions with the linked audio, the default, first
+    pub fn versions(&self, linked_audio: &str) -> Vec<AudioVersion> {
+        let mut versions = vec![AudioVersion {
+            label: self
+                .audio_label
+                .clone()
+                .unwrap_or(DEFAULT_AUDIO_LABEL.to_owned()),
+            path: linked_audio.to_owned(),
+        }];
+        versions.extend(self.audio_versions.iter().cloned());
+        versions
+    }
+
+    /// Add an alternate version, replacing any version with the same path
+    pub fn add_version(&mut self, label: &str, path: &str) {
+        self.audio_versions.retain(|v| v.path != path);
+        self.audio_versions.push(AudioVersion {
+            label: label.to_owned(),
+            path: path.to_owned(),
+        });
+    }
+
+    /// Make an alternate version the default. The linked audio becomes an alternate version
+    ///
+    /// Returns false if `path` is not an alternate version
+    pub fn make_default(&mut self, linked_audio: &str, path: &str) -> bool {
+        let Some(index) = self.audio_versions.iter().position(|v| v.path == path) else {
+            return false;
+        };
+        let new_default = self.audio_versions.remove(index);
+        let old_label = self
+            .audio_label
+            .replace(new_default.label)
+            .unwrap_or(DEFAULT_AUDIO_LABEL.to_owned());
+        self.add_version(&old_label, linked_audio);
+        true
+    }
+
+    /// Weight used when picking a random entry. Unrated entries count as 3 stars and favourites
+    /// are picked more often. Ratings out of range, which a hand-edited meta file may have, are
+    /// clamped so the weight is never 0
+    pub fn random_weight(&self) -> u32 {
+        let rating = self.rating.unwrap_or(3).clamp(1, MAX_RATING) as u32;
+        if self.favourite {
+            rating + MAX_RATING as u32
+        } else {
+            rating
+        }
+    }
+}
+
+/// Path of the meta file that belongs to the given data file
+pub fn meta_file_for(data_file: &str) -> String {
+    format!("{}.meta", data_file)
+}
+
+/// Directory for generated files, such as subtitles, that belongs to the given data file
+pub fn cache_dir_for(data_file: &str) -> String {
+    format!("{}.cache", data_file)
+}
+
+/// Load the meta store. A missing file is treated as an empty store
+///
+/// # Panics
+/// Panics if the file exists but cannot be parsed
+pub fn load_meta(meta_file: &str) -> MetaStore {
+    if !Path::new(meta_file).exists() {
+        return MetaStore::new();
+    }
+    let data = fs::read_to_string(meta_file).expect("Unable to read meta file");
+    serde_json::from_str::<MetaStore>(&data).expect("Unable to parse meta file")
+}
+
+/// Save the meta store, leaving out entries that have no data. It is written through a temporary
+/// file so it is never left half written
+pub fn save_meta(meta_file: &str, meta: &MetaStore) -> std::io::Result<()> {
+    let to_save = meta
+        .iter()
+        .filter(|(_, m)| !m.is_empty())
+        .collect::<HashMap<&String, &EntryMeta>>();
+    let data = serde_json::to_string_pretty(&to_save)?;
+    let temp_file = format!("{}.tmp", meta_file);
+    let mut file = fs::File::create(&temp_file)?;
+    file.write_all(data.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temp_file, meta_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_toggle_tag() {
+        let mut meta = EntryMeta::default();
+        meta.toggle_tag(" choreo ");
+        meta.toggle_tag("2nd gen");
+        assert!(meta.tags.contains("choreo"));
+        meta.toggle_tag("choreo");
+        assert!(!meta.tags.contains("choreo"));
+        meta.toggle_tag("  ");
+        assert_eq!(meta.tags.len(), 1);
+    }
+
+    #[test]
+    fn test_set_rating() {
+        let mut meta = EntryMeta::default();
+        meta.set_rating(Some(4));
+        assert_eq!(meta.rating, Some(4));
+        meta.set_rating(Some(6));
+        assert_eq!(meta.rating, None);
+        meta.set_rating(Some(0));
+        assert_eq!(meta.rating, None);
+    }
+
+    #[test]
+    fn test_random_weight() {
+        let mut meta = EntryMeta::default();
+        assert_eq!(meta.random_weight(), 3);
+        meta.favourite = true;
+        assert_eq!(meta.random_weight(), 8);
+        // Loaded from a hand-edited meta file
+        let meta: EntryMeta = serde_json::from_str(r#"{"rating": 0}"#).unwrap();
+        assert_eq!(meta.random_weight(), 1);
+    }
+
+    #[test]
+    fn test_make_default() {
+        let mut meta = EntryMeta::default();
+        meta.add_version("Japanese", "audio/jp.flac");
+        meta.add_version("Live", "audio/live.flac");
+        assert!(!meta.make_default("audio/og.flac", "audio/og.flac"));
+        assert!(meta.make_default("audio/og.flac", "audio/jp.flac"));
+        assert_eq!(meta.audio_label, Some("Japanese".to_string()));
+        let versions = meta.versions("audio/jp.flac");
+        assert_eq!(versions.len(), 3);
+        assert_eq!(versions[0].label, "Japanese");
+        assert_eq!(versions[2].label, DEFAULT_AUDIO_LABEL);
+        assert_eq!(versions[2].path, "audio/og.flac");
+    }
+
+    #[test]
+    fn test_save_load_meta() {
+        let temp_dir = TempDir::new("test_save_load_meta").unwrap();
+        let meta_file = temp_dir.path().join("data.json.meta");
+        let meta_file = meta_file.to_str().unwrap();
+        assert!(load_meta(meta_file).is_empty());
+        let mut store = MetaStore::new();
+        let mut meta = EntryMeta::default();
+        meta.toggle_tag("choreo");
+        meta.favourite = true;
+        store.insert("video/1.mp4".to_string(), meta.clone());
+        store.insert("video/2.mp4".to_string(), EntryMeta::default());
+        save_meta(meta_file, &store).unwrap();
+        let loaded = load_meta(meta_file);
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded.get("video/1.mp4"), Some(&meta));
+    }
+}
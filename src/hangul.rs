@@ -0,0 +1,153 @@
+//! Search keys for Korean text: Revised Romanization and choseong (initial consonant) strings
+
+const SYLLABLE_START: u32 = 0xAC00;
+const SYLLABLE_END: u32 = 0xD7A3;
+const JUNGSEONG_COUNT: u32 = 21;
+const JONGSEONG_COUNT: u32 = 28;
+
+/// Compatibility jamo for each initial consonant, these are what a keyboard produces when typing
+/// a lone consonant
+const CHOSEONG_JAMO: [char; 19] = [
+    'ㄱ', 'ㄲ', 'ㄴ', 'ㄷ', 'ㄸ', 'ㄹ', 'ㅁ', 'ㅂ', 'ㅃ', 'ㅅ', 'ㅆ', 'ㅇ', 'ㅈ', 'ㅉ', 'ㅊ', 'ㅋ',
+    'ㅌ', 'ㅍ', 'ㅎ',
+];
+
+const INITIALS: [&str; 19] = [
+    "g", "kk", "n", "d", "tt", "r", "m", "b", "pp", "s", "ss", "", "j", "jj", "ch", "k", "t", "p",
+    "h",
+];
+
+const MEDIALS: [&str; 21] = [
+    "a", "ae", "ya", "yae", "eo", "e", "yeo", "ye", "o", "wa", "wae", "oe", "yo", "u", "wo", "we",
+    "wi", "yu", "eu", "ui", "i",
+];
+
+/// Finals at the end of a word or before a consonant
+const FINALS: [&str; 28] = [
+    "", "k", "k", "k", "n", "n", "n", "t", "l", "k", "m", "l", "l", "l", "p", "l", "m", "p", "p",
+    "t", "t", "ng", "t", "t", "k", "t", "p", "t",
+];
+
+/// Finals that are carried over to the next syllable when it starts with a silent ㅇ
+const LINKED_FINALS: [&str; 28] = [
+    "", "g", "kk", "ks", "n", "nj", "nh", "d", "r", "lg", "lm", "lb", "ls", "lt", "lp", "lh", "m",
+    "b", "bs", "s", "ss", "ng", "j", "ch", "k", "t", "p", "",
+];
+
+const SILENT_INITIAL: u32 = 11;
+const RIEUL_INITIAL: u32 = 5;
+const RIEUL_FINAL: u32 = 8;
+const NIEUN_FINAL: u32 = 4;
+
+/// Split a Hangul syllable into its initial, medial and final indices
+fn decompose(c: char) -> Option<(u32, u32, u32)> {
+    let code = c as u32;
+    if !(SYLLABLE_START..=SYLLABLE_END).contains(&code) {
+        return None;
+    }
+    let index = code - SYLLABLE_START;
+    Some((
+        index / (JUNGSEONG_COUNT * JONGSEONG_COUNT),
+        (index % (JUNGSEONG_COUNT * JONGSEONG_COUNT)) / JONGSEONG_COUNT,
+        index % JONGSEONG_COUNT,
+    ))
+}
+
+pub fn contains_hangul(text: &str) -> bool {
+    text.chars().any(|c| decompose(c).is_some())
+}
+
+/// Romanize the Hangul in the text with the Revised Romanization of Korean. Other characters are
+/// kept as they are. Only liaison and ㄹㄹ assimilation are applied, which is enough for searching
+pub fn romanize(text: &str) -> String {
+    let chars = text.chars().collect::<Vec<char>>();
+    let mut romanized = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        let Some((initial, medial, final_)) = decompose(*c) else {
+            romanized.push(*c);
+            continue;
+        };
+        let previous = i.checked_sub(1).and_then(|p| decompose(chars[p]));
+        let next = chars.get(i + 1).and_then(|n| decompose(*n));
+        match previous {
+            // The final of the previous syllable was carried over
+            Some((_, _, prev_final)) if initial == SILENT_INITIAL && prev_final != 0 => {}
+            Some((_, _, prev_final))
+                if initial == RIEUL_INITIAL
+                    && (prev_final == RIEUL_FINAL || prev_final == NIEUN_FINAL) =>
+            {
+                romanized.push('l')
+            }
+            _ => romanized.push_str(INITIALS[initial as usize]),
+        }
+        romanized.push_str(MEDIALS[medial as usize]);
+        match next {
+            Some((SILENT_INITIAL, _, _)) => romanized.push_str(LINKED_FINALS[final_ as usize]),
+            Some((RIEUL_INITIAL, _, _)) if final_ == NIEUN_FINAL => romanized.push('l'),
+            _ => romanized.push_str(FINALS[final_ as usize]),
+        }
+    }
+    romanized
+}
+
+/// The initial consonant of every Hangul syllable, words are kept apart by spaces and other
+/// characters are dropped. "아이유 좋은 날" becomes "ㅇㅇㅇ ㅈㅇ ㄴ"
+pub fn choseong(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter_map(decompose)
+                .map(|(initial, _, _)| CHOSEONG_JAMO[initial as usize])
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Romanization and choseong of the text, or None if it contains no Hangul
+pub fn search_keys(text: &str) -> Option<String> {
+    if !contains_hangul(text) {
+        return None;
+    }
+    let romanized = romanize(text);
+    Some(format!(
+        "{} {} {}",
+        romanized,
+        romanized.replace(' ', ""),
+        choseong(text)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_romanize() {
+        assert_eq!(romanize("아이유"), "aiyu");
+        assert_eq!(romanize("좋은 날"), "joeun nal");
+        assert_eq!(romanize("한국어"), "hangugeo");
+        assert_eq!(romanize("신라"), "silla");
+        assert_eq!(
+            romanize("블랙핑크 - 뚜두뚜두"),
+            "beullaekpingkeu - ttuduttudu"
+        );
+        assert_eq!(romanize("IU"), "IU");
+    }
+
+    #[test]
+    fn test_choseong() {
+        assert_eq!(choseong("아이유"), "ㅇㅇㅇ");
+        assert_eq!(choseong("아이유 - 좋은 날 (Live)"), "ㅇㅇㅇ ㅈㅇ ㄴ");
+    }
+
+    #[test]
+    fn test_search_keys() {
+        assert_eq!(search_keys("Blueming"), None);
+        assert_eq!(
+            search_keys("좋은 날").unwrap(),
+            "joeun nal joeunnal ㅈㅇ ㄴ"
+        );
+    }
+}
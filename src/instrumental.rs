@@ -0,0 +1,136 @@
+use super::probe;
+use std::path::Path;
+
+/// Title and album of an audio file, from its tags or else from its file and folder name
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackInfo {
+    pub path: String,
+    pub title: String,
+    pub album: String,
+}
+
+impl TrackInfo {
+    /// Build the info from the file and folder name only
+    pub fn from_path(path: &str) -> Self {
+        let file_path = Path::new(path);
+        let title = file_path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_owned();
+        let album = file_path
+            .parent()
+            .and_then(|p| p.file_name())
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_owned();
+        Self {
+            path: path.to_owned(),
+            title,
+            album,
+        }
+    }
+
+    /// Build the info from the tags, falling back to the file and folder name
+    pub fn read(path: &str) -> Self {
+        let mut info = Self::from_path(path);
+        if let Some(probed) = probe::probe(path) {
+            if let Some(title) = probed.tag("title") {
+                info.title = title.to_owned();
+            }
+            if let Some(album) = probed.tag("album") {
+                info.album = album.to_owned();
+            }
+        }
+        info
+    }
+
+    fn same_folder(&self, other: &TrackInfo) -> bool {
+        Path::new(&self.path).parent() == Path::new(&other.path).parent()
+    }
+}
+
+const INSTRUMENTAL_MARKERS: [&str; 5] = ["(inst", "[inst", "- inst", "instrumental", "(mr)"];
+
+pub fn is_instrumental(title: &str) -> bool {
+    let title = title.to_lowercase();
+    INSTRUMENTAL_MARKERS.iter().any(|m| title.contains(m))
+}
+
+/// The title without track number, instrumental markers and punctuation, used to match a track
+/// with its instrumental
+pub fn base_title(title: &str) -> String {
+    let mut title = title.to_lowercase();
+    for marker in INSTRUMENTAL_MARKERS {
+        if let Some(start) = title.find(marker) {
+            let end = match marker.chars().next() {
+                Some('(') => title[start..].find(')').map(|e| start + e + 1),
+                Some('[') => title[start..].find(']').map(|e| start + e + 1),
+                _ => None,
+            }
+            .unwrap_or(start + marker.len());
+            title.replace_range(start..end, " ");
+        }
+    }
+    // Track numbers such as "01 ", "01. " or "1-01 "
+    let title = title.trim_start_matches(|c: char| c.is_ascii_digit() || "-. _".contains(c));
+    title.chars().filter(|c| c.is_alphanumeric()).collect()
+}
+
+/// Find the instrumental version of the track among the candidates. It must have the same base
+/// title and be on the same album or in the same folder
+pub fn find_instrumental(track: &TrackInfo, candidates: &[TrackInfo]) -> Option<String> {
+    let title = base_title(&track.title);
+    if title.is_empty() || is_instrumental(&track.title) {
+        return None;
+    }
+    candidates
+        .iter()
+        .filter(|c| c.path != track.path && is_instrumental(&c.title))
+        .filter(|c| base_title(&c.title) == title)
+        .find(|c| c.same_folder(track) || (!c.album.is_empty() && c.album == track.album))
+        .map(|c| c.path.to_owned())
+}
+
+/// Candidates for instrumentals among the audio files. Only files whose name marks them as an
+/// instrumental are read, to avoid reading the tags of the whole library
+pub fn instrumental_candidates(audio_files: &[String]) -> Vec<TrackInfo> {
+    audio_files
+        .iter()
+        .filter(|path| is_instrumental(&TrackInfo::from_path(path).title))
+        .map(|path| TrackInfo::read(path))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_title() {
+        assert_eq!(base_title("01. Blueming"), "blueming");
+        assert_eq!(base_title("05 Blueming (Inst.)"), "blueming");
+        assert_eq!(base_title("Blueming [Instrumental]"), "blueming");
+        assert_eq!(base_title("좋은 날 (MR)"), "좋은날");
+    }
+
+    #[test]
+    fn test_find_instrumental() {
+        let track = TrackInfo::from_path("/music/Love poem/01 Blueming.flac");
+        let candidates = vec![
+            TrackInfo::from_path("/music/Other/05 Blueming (Inst.).flac"),
+            TrackInfo::from_path("/music/Love poem/06 Blueming (Inst.).flac"),
+            TrackInfo::from_path("/music/Love poem/07 Love poem (Inst.).flac"),
+        ];
+        assert_eq!(
+            find_instrumental(&track, &candidates),
+            Some("/music/Love poem/06 Blueming (Inst.).flac".to_string())
+        );
+        let mut tagged = TrackInfo::from_path("/music/Other/05 Blueming (Inst.).flac");
+        tagged.album = "Love poem".to_string();
+        assert_eq!(
+            find_instrumental(&track, &[tagged]),
+            Some("/music/Other/05 Blueming (Inst.).flac".to_string())
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use super::unicode_paths;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Problems found in the library
+/// # Fields
+/// * `unlinked_videos`: Videos in the video directory that are not linked to any audio
+/// * `shared_audio`: Audio files linked to more than one video, with the videos they are linked to
+/// * `unused_audio`: Audio files in the audio directory that nothing is linked to
+/// * `decode_failures`: Linked files that are missing or fail a quick decode
+/// * `outside_roots`: Entries whose video or audio is outside the configured directories
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct IntegrityReport {
+    pub unlinked_videos: Vec<String>,
+    pub shared_audio: BTreeMap<String, Vec<String>>,
+    pub unused_audio: Vec<String>,
+    pub decode_failures: Vec<DecodeFailure>,
+    pub outside_roots: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DecodeFailure {
+    pub path: String,
+    pub error: String,
+}
+
+impl IntegrityReport {
+    /// Compare the links with the files on disk
+    ///
+    /// `other_audio` are audio files that are used without being the linked audio, such as
+    /// alternate versions and instrumentals
+    pub fn new(
+        video_dir: &str,
+        audio_dir: &str,
+        audio_video: &HashMap<String, String>,
+        other_audio: &BTreeSet<String>,
+        video_files: &[String],
+        audio_files: &[String],
+    ) -> Self {
+        let linked_videos = audio_video
+            .keys()
+            .map(|video| unicode_paths::nfc(video))
+            .collect::<HashSet<String>>();
+        let mut unlinked_videos = video_files
+            .iter()
+            .filter(|video| !linked_videos.contains(&unicode_paths::nfc(video)))
+            .cloned()
+            .collect::<Vec<String>>();
+        unlinked_videos.sort();
+
+        let mut by_audio = BTreeMap::<String, Vec<String>>::new();
+        for (video, audio) in audio_video {
+            by_audio
+                .entry(unicode_paths::nfc(audio))
+                .or_default()
+                .push(video.to_owned());
+        }
+        let used_audio = by_audio
+            .keys()
+            .cloned()
+            .chain(other_audio.iter().map(|audio| unicode_paths::nfc(audio)))
+            .collect::<HashSet<String>>();
+        let mut unused_audio = audio_files
+            .iter()
+            .filter(|audio| !used_audio.contains(&unicode_paths::nfc(audio)))
+            .cloned()
+            .collect::<Vec<String>>();
+        unused_audio.sort();
+        let shared_audio = by_audio
+            .into_iter()
+            .filter(|(_, videos)| videos.len() > 1)
+            .map(|(audio, mut videos)| {
+                videos.sort();
+                (audio, videos)
+            })
+            .collect();
+
+        let mut outside_roots = audio_video
+            .iter()
+            .filter(|(video, audio)| {
+                !Path::new(video).starts_with(video_dir) || !Path::new(audio).starts_with(audio_dir)
+            })
+            .map(|(video, _)| video.to_owned())
+            .collect::<Vec<String>>();
+        outside_roots.sort();
+
+        Self {
+            unlinked_videos,
+            shared_audio,
+            unused_audio,
+            decode_failures: Vec::new(),
+            outside_roots,
+        }
+    }
+
+    /// Decode the start of every linked video and audio file
+    pub fn check_decodes(&mut self, audio_video: &HashMap<String, String>) {
+        let paths = audio_video
+            .iter()
+            .flat_map(|(video, audio)| [video.to_owned(), audio.to_owned()])
+            .collect::<BTreeSet<String>>();
+        for (i, path) in paths.iter().enumerate() {
+            // Progress goes to stderr so the JSON output can be piped
+            eprintln!("Decode check {}/{}: {}", i + 1, paths.len(), path);
+            if let Err(error) = decode_check(path) {
+                self.decode_failures.push(DecodeFailure {
+                    path: path.to_owned(),
+                    error,
+                });
+            }
+        }
+    }
+
+    pub fn issue_count(&self) -> usize {
+        self.unlinked_videos.len()
+            + self.shared_audio.len()
+            + self.unused_audio.len()
+            + self.decode_failures.len()
+            + self.outside_roots.len()
+    }
+
+    /// A summary table followed by the paths for each check that found something
+    pub fn table(&self) -> Vec<String> {
+        let counts = [
+            ("Unlinked videos", self.unlinked_videos.len()),
+            ("Audio linked to several videos", self.shared_audio.len()),
+            ("Audio never linked", self.unused_audio.len()),
+            ("Decode failures", self.decode_failures.len()),
+            ("Entries outside the roots", self.outside_roots.len()),
+        ];
+        let mut lines = vec![format!("{:<32}{:>6}", "Check", "Count")];
+        lines.extend(
+            counts
+                .iter()
+                .map(|(check, count)| format!("{:<32}{:>6}", check, count)),
+        );
+        let mut section = |title: &str, rows: Vec<String>| {
+            if !rows.is_empty() {
+                lines.push(String::new());
+                lines.push(title.to_owned());
+                lines.extend(rows.into_iter().map(|row| format!("  {}", row)));
+            }
+        };
+        section(counts[0].0, self.unlinked_videos.clone());
+        section(
+            counts[1].0,
+            self.shared_audio
+                .iter()
+                .map(|(audio, videos)| format!("{} <- {}", audio, videos.join(", ")))
+                .collect(),
+        );
+        section(counts[2].0, self.unused_audio.clone());
+        section(
+            counts[3].0,
+            self.decode_failures
+                .iter()
+                .map(|failure| format!("{}: {}", failure.path, failure.error))
+                .collect(),
+        );
+        section(counts[4].0, self.outside_roots.clone());
+        lines
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+/// Decode the first seconds of the file with ffmpeg. Returns the first error ffmpeg printed
+pub fn decode_check(path: &str) -> Result<(), String> {
+    if !Path::new(path).exists() {
+        return Err("File not found".to_owned());
+    }
+    let output = Command::new("ffmpeg")
+        .args([
+            "-nostats",
+            "-hide_banner",
+            "-v",
+            "error",
+            "-xerror",
+            "-t",
+            "5",
+            "-i",
+        ])
+        .arg(path)
+        .args(["-f", "null", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .output()
+        .map_err(|e| format!("Couldn't run ffmpeg: {}", e))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match stderr.lines().find(|line| !line.trim().is_empty()) {
+        Some(error) => Err(error.trim().to_owned()),
+        None if !output.status.success() => Err(format!("ffmpeg exited with {}", output.status)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integrity_report() {
+        let audio_video = HashMap::from([
+            ("/mv/a.mp4".to_owned(), "/music/a.flac".to_owned()),
+            ("/mv/b.mp4".to_owned(), "/music/a.flac".to_owned()),
+            ("/elsewhere/c.mp4".to_owned(), "/music/c.flac".to_owned()),
+        ]);
+        let other_audio = BTreeSet::from(["/music/a (Inst.).flac".to_owned()]);
+        let video_files = ["/mv/a.mp4", "/mv/b.mp4", "/mv/d.mp4"].map(String::from);
+        let audio_files = [
+            "/music/a.flac",
+            "/music/a (Inst.).flac",
+            "/music/c.flac",
+            "/music/e.mp3",
+        ]
+        .map(String::from);
+        let report = IntegrityReport::new(
+            "/mv",
+            "/music",
+            &audio_video,
+            &other_audio,
+            &video_files,
+            &audio_files,
+        );
+        assert_eq!(report.unlinked_videos, vec!["/mv/d.mp4"]);
+        assert_eq!(
+            report.shared_audio,
+            BTreeMap::from([(
+                "/music/a.flac".to_owned(),
+                vec!["/mv/a.mp4".to_owned(), "/mv/b.mp4".to_owned()]
+            )])
+        );
+        assert_eq!(report.unused_audio, vec!["/music/e.mp3"]);
+        assert_eq!(report.outside_roots, vec!["/elsewhere/c.mp4"]);
+        assert_eq!(report.issue_count(), 4);
+        assert_eq!(
+            report.table()[2],
+            format!("{:<32}{:>6}", "Audio linked to several videos", 1)
+        );
+        assert!(report
+            .to_json()
+            .contains("\"unused_audio\": [\n    \"/music/e.mp3\"\n  ]"));
+        assert_eq!(
+            decode_check("/nonexistent.flac"),
+            Err("File not found".to_owned())
+        );
+    }
+}
@@ -1,6 +1,26 @@
 pub mod avmod;
 pub mod config;
+pub mod duplicates;
+pub mod entry_meta;
+pub mod hangul;
+pub mod instrumental;
+pub mod integrity;
+pub mod loudness;
+pub mod lyrics;
 pub mod media_player;
+pub mod mpv_ipc;
+pub mod mv_name;
+pub mod play_queue;
+pub mod player_command;
+pub mod player_processes;
+pub mod player_sync;
+pub mod probe;
+pub mod quality;
+pub mod release_date;
+pub mod renamer;
+pub mod tempo;
+pub mod thumbnails;
+pub mod unicode_paths;
 pub mod views;
 
 use std::cell::RefCell;
@@ -9,6 +29,8 @@ use std::sync::Arc;
 
 use avmod::{AudioVideoData, Sorting};
 use config::Config;
+use mv_name::AliasTable;
+use thumbnails::ImageProtocol;
 use views::menu::{MainMenu, MenuOptions};
 use views::mv_selector::{FilterTypes, MVSelector};
 use views::search_filter::SearchFilters;
@@ -21,24 +43,104 @@ use views::updater::Updater;
 // const VPATH_PREFIX: &str = "F:\\Music\\MVs\\";
 // const APATH_PREFIX: &str = "F:\\";
 
-pub async fn run() {
-    let config = Config::build("config.yml").unwrap();
-    let audio_video = Arc::new(RefCell::new(HashMap::new()));
+/// Build the library from the config and load the data file
+fn load_library(
+    config: &Config,
+    audio_video: Arc<RefCell<HashMap<String, String>>>,
+) -> AudioVideoData {
     let mut avd = AudioVideoData::new(
         config.data_file.as_str(),
         config.video_dir.to_string(),
         config.audio_dir.to_string(),
-        audio_video.clone(),
+        audio_video,
         config.video_cmd.to_string(),
         config.audio_cmd.to_string(),
     );
+    avd.aliases = AliasTable::new(&config.artist_aliases);
+    avd.subtitle_arg = config.subtitle_arg.to_string();
+    avd.quality_policy = config.quality_policy();
+    avd.target_loudness = config.target_loudness;
+    avd.gain_arg = config.gain_arg.to_string();
+    if let Some(trash_dir) = &config.trash_dir {
+        avd.trash_dir = trash_dir.to_string();
+    }
+    avd.rename_template = config.rename_template.to_string();
+    avd.player_profiles = config.player_profiles.clone();
+    avd.set_player(media_player::backend_from_config(
+        &config.player_backend,
+        &config.video_cmd,
+        &config.audio_cmd,
+        config.drift_threshold_ms,
+        &config.fallback_video_cmds,
+        &config.fallback_audio_cmds,
+    ));
+    avd.image_protocol = ImageProtocol::from_config(config.preview_protocol.as_deref());
     avd.load_data();
+    avd
+}
+
+/// Audit the library without the UI and print the report as a table, or as JSON with `--json`.
+/// `--no-decode` skips the decode check
+///
+/// Returns the exit code, 1 when problems were found so it can be used from cron
+pub fn run_integrity(args: &[String]) -> i32 {
+    let config = Config::build("config.yml").unwrap();
+    #[allow(clippy::arc_with_non_send_sync)]
+    let avd = load_library(&config, Arc::new(RefCell::new(HashMap::new())));
+    let report = avd.integrity_report(!args.iter().any(|arg| arg == "--no-decode"));
+    if args.iter().any(|arg| arg == "--json") {
+        println!("{}", report.to_json());
+    } else {
+        println!("{}", report.table().join("\n"));
+    }
+    if report.issue_count() > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+/// Resolves on Ctrl-C, or SIGTERM on Unix, with the number of the signal
+async fn exit_signal() -> i32 {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).expect("failed to listen for SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => libc::SIGINT,
+            _ = terminate.recv() => libc::SIGTERM,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await.ok();
+        2
+    }
+}
+
+pub async fn run() {
+    let config = Config::build("config.yml").unwrap();
+    #[allow(clippy::arc_with_non_send_sync)]
+    let audio_video = Arc::new(RefCell::new(HashMap::new()));
+    let avd = load_library(&config, audio_video.clone());
+    player_processes::init(&player_processes::pid_file_for(&config.data_file));
+    tokio::spawn(async {
+        let signal = exit_signal().await;
+        // Killing waits for the players to exit
+        tokio::task::spawn_blocking(player_processes::kill_all)
+            .await
+            .ok();
+        std::process::exit(128 + signal);
+    });
+    avd.generate_thumbnails();
     let mut mv_selector = MVSelector::new(avd);
     let mut selected_opt: MenuOptions = MenuOptions::MVSelector;
     loop {
         match selected_opt {
             MenuOptions::Quit => {
                 println!("Exiting...");
+                mv_selector.avd.stop_playback();
+                player_processes::kill_all();
                 break;
             }
             MenuOptions::MainMenu => {
@@ -54,45 +156,23 @@ pub async fn run() {
                 selected_opt = mv_selector.toggle_filter(FilterTypes::MVs);
             }
             MenuOptions::SortAsc => {
-                match mv_selector.avd.sorting {
-                    Sorting::Ascending => {}
-                    Sorting::Descending => {
-                        mv_selector.avd.sorting = Sorting::Ascending;
-                        mv_selector.avd.video_list = None;
-                    }
-                    Sorting::Mtime => {
-                        mv_selector.avd.sorting = Sorting::Ascending;
-                        mv_selector.avd.video_list = None;
-                    }
-                }
+                mv_selector.avd.set_sorting(Sorting::Ascending);
                 selected_opt = MenuOptions::MVSelector;
             }
             MenuOptions::SortDesc => {
-                match mv_selector.avd.sorting {
-                    Sorting::Ascending => {
-                        mv_selector.avd.sorting = Sorting::Descending;
-                        mv_selector.avd.video_list = None;
-                    }
-                    Sorting::Descending => {}
-                    Sorting::Mtime => {
-                        mv_selector.avd.sorting = Sorting::Descending;
-                        mv_selector.avd.video_list = None;
-                    }
-                }
+                mv_selector.avd.set_sorting(Sorting::Descending);
                 selected_opt = MenuOptions::MVSelector;
             }
             MenuOptions::SortMtime => {
-                match mv_selector.avd.sorting {
-                    Sorting::Ascending => {
-                        mv_selector.avd.sorting = Sorting::Mtime;
-                        mv_selector.avd.video_list = None;
-                    }
-                    Sorting::Descending => {
-                        mv_selector.avd.sorting = Sorting::Mtime;
-                        mv_selector.avd.video_list = None;
-                    }
-                    Sorting::Mtime => {}
-                }
+                mv_selector.avd.set_sorting(Sorting::Mtime);
+                selected_opt = MenuOptions::MVSelector;
+            }
+            MenuOptions::SortReleaseDate => {
+                mv_selector.avd.set_sorting(Sorting::ReleaseDate);
+                selected_opt = MenuOptions::MVSelector;
+            }
+            MenuOptions::SortBpm => {
+                mv_selector.avd.set_sorting(Sorting::Bpm);
                 selected_opt = MenuOptions::MVSelector;
             }
             MenuOptions::Update => {
@@ -100,9 +180,11 @@ pub async fn run() {
                     config.video_dir.to_string(),
                     config.audio_dir.to_string(),
                     audio_video.clone(),
+                    mv_selector.avd.meta.clone(),
                 );
+                updater.quality_policy = config.quality_policy();
                 selected_opt = updater.start();
-                mv_selector.avd.save_data();
+                mv_selector.save_data();
                 mv_selector.avd.video_list = None;
             }
             MenuOptions::Random => {
@@ -112,7 +194,8 @@ pub async fn run() {
                 match &mv_selector.avd.search_filtered_list {
                     None => {
                         let av = mv_selector.filtered_list();
-                        let mut search_filter = SearchFilters::new(av);
+                        let search_keys = mv_selector.avd.search_keys(&av);
+                        let mut search_filter = SearchFilters::new(av, search_keys);
                         let selected_videos = search_filter.start();
                         mv_selector.set_search_filters(Some(selected_videos.to_owned()));
                     }
@@ -124,11 +207,87 @@ pub async fn run() {
                 mv_selector.played_list.clear();
                 selected_opt = MenuOptions::MVSelector;
             }
+            MenuOptions::EditEntry => {
+                selected_opt = mv_selector.edit_entry();
+            }
+            MenuOptions::ToggleFavourites => {
+                selected_opt = mv_selector.toggle_filter(FilterTypes::Favourites);
+            }
+            MenuOptions::TagFilter => {
+                selected_opt = mv_selector.choose_tag_filter();
+            }
+            MenuOptions::RatingFilter => {
+                selected_opt = mv_selector.choose_rating_filter();
+            }
+            MenuOptions::ArtistReport => {
+                selected_opt = mv_selector.artist_report();
+            }
+            MenuOptions::UnicodeReport => {
+                selected_opt = mv_selector.unicode_report();
+            }
+            MenuOptions::BrowseEras => {
+                selected_opt = mv_selector.browse_eras();
+            }
+            MenuOptions::OnThisDay => {
+                selected_opt = mv_selector.on_this_day();
+            }
+            MenuOptions::PlayNext => {
+                selected_opt = mv_selector.play_next().await;
+            }
+            MenuOptions::ToggleLyrics => {
+                selected_opt = mv_selector.toggle_lyrics();
+            }
+            MenuOptions::ToggleKaraoke => {
+                selected_opt = mv_selector.toggle_karaoke();
+            }
+            MenuOptions::DetectInstrumentals => {
+                selected_opt = mv_selector.detect_instrumentals();
+            }
+            MenuOptions::PlayVersion => {
+                selected_opt = mv_selector.play_version().await;
+            }
+            MenuOptions::QualityReport => {
+                selected_opt = mv_selector.quality_report();
+            }
+            MenuOptions::Duplicates => {
+                selected_opt = mv_selector.duplicates();
+            }
+            MenuOptions::AnalyzeLoudness => {
+                selected_opt = mv_selector.analyze_loudness();
+            }
+            MenuOptions::AnalyzeTempo => {
+                selected_opt = mv_selector.analyze_tempo();
+            }
+            MenuOptions::BpmFilter => {
+                selected_opt = mv_selector.choose_bpm_filter();
+            }
+            MenuOptions::IntegrityReport => {
+                selected_opt = mv_selector.integrity_report();
+            }
+            MenuOptions::RenameMVs => {
+                selected_opt = mv_selector.rename_mvs();
+            }
+            MenuOptions::Stop => {
+                selected_opt = mv_selector.stop();
+            }
+            MenuOptions::PlayerControls => {
+                selected_opt = mv_selector.player_controls();
+            }
+            MenuOptions::ContinuousPlay => {
+                selected_opt = mv_selector.continuous_play().await;
+            }
+            MenuOptions::AddToQueue => {
+                selected_opt = mv_selector.add_to_queue();
+            }
+            MenuOptions::Queue => {
+                selected_opt = mv_selector.show_queue().await;
+            }
         }
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::arc_with_non_send_sync)]
 mod tests {
     use std::collections::HashMap;
     use std::sync::Arc;
@@ -0,0 +1,86 @@
+use super::probe;
+use std::process::{Command, Stdio};
+
+/// Loudness that ReplayGain gains are relative to, in LUFS
+const REPLAYGAIN_REFERENCE: f64 = -18.0;
+
+/// Largest gain that is applied either way, in dB
+const MAX_GAIN: f64 = 20.0;
+
+/// Read the integrated loudness from the summary the ebur128 filter prints at the end
+pub fn parse_ebur128(output: &str) -> Option<f64> {
+    let summary = &output[output.rfind("Integrated loudness:")?..];
+    summary
+        .lines()
+        .map(|line| line.trim())
+        .find_map(|line| line.strip_prefix("I:"))
+        .and_then(|value| value.trim().trim_end_matches("LUFS").trim().parse().ok())
+}
+
+/// Parse a ReplayGain tag value like "-6.54 dB"
+fn parse_gain_tag(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .trim_end_matches("dB")
+        .trim_end_matches("db")
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Integrated loudness in LUFS from the ReplayGain track gain tag
+pub fn loudness_from_tags(path: &str) -> Option<f64> {
+    let info = probe::probe(path)?;
+    let gain = info.tag("replaygain_track_gain").and_then(parse_gain_tag)?;
+    Some(REPLAYGAIN_REFERENCE - gain)
+}
+
+/// Measure the integrated loudness in LUFS with ffmpeg's ebur128 filter
+pub fn measure_loudness(path: &str) -> Option<f64> {
+    let output = Command::new("ffmpeg")
+        .args(["-nostats", "-hide_banner", "-i"])
+        .arg(path)
+        .args(["-map", "0:a:0", "-filter:a", "ebur128", "-f", "null", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .output()
+        .ok()?;
+    parse_ebur128(std::str::from_utf8(&output.stderr).ok()?)
+}
+
+/// Gain in dB that brings audio at `loudness` to `target`
+pub fn gain_for(loudness: f64, target: f64) -> f64 {
+    ((target - loudness) * 100.0)
+        .round()
+        .clamp(-MAX_GAIN * 100.0, MAX_GAIN * 100.0)
+        / 100.0
+}
+
+/// Gain in dB for the file. ReplayGain tags are used when present, otherwise the file is measured
+pub fn analyze(path: &str, target: f64) -> Option<f64> {
+    loudness_from_tags(path)
+        .or_else(|| measure_loudness(path))
+        .map(|loudness| gain_for(loudness, target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ebur128() {
+        let output = "[Parsed_ebur128_0 @ 0x1] t: 0.1 M: -120.7 S: -120.7 I: -70.0 LUFS\n\
+                      [Parsed_ebur128_0 @ 0x1] Summary:\n\n  \
+                      Integrated loudness:\n    I:         -14.2 LUFS\n    Threshold: -24.5 LUFS\n\n  \
+                      Loudness range:\n    LRA:         5.1 LU\n";
+        assert_eq!(parse_ebur128(output), Some(-14.2));
+        assert_eq!(parse_ebur128("no summary"), None);
+    }
+
+    #[test]
+    fn test_gain_for() {
+        assert_eq!(gain_for(-14.2, -18.0), -3.8);
+        assert_eq!(gain_for(-60.0, -18.0), 20.0);
+        assert_eq!(parse_gain_tag("-6.54 dB"), Some(-6.54));
+    }
+}
@@ -0,0 +1,347 @@
+use super::probe;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// A line of synced lyrics
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LyricLine {
+    pub start_ms: i64,
+    pub text: String,
+}
+
+/// How long the last line is shown for
+const LAST_LINE_MS: i64 = 5000;
+
+/// Parse a timestamp like "01:02.34" or "01:02" into milliseconds
+fn parse_timestamp(stamp: &str) -> Option<i64> {
+    let (minutes, seconds) = stamp.split_once(':')?;
+    let minutes = minutes.trim().parse::<i64>().ok()?;
+    let seconds = seconds.trim().replace(':', ".").parse::<f64>().ok()?;
+    if seconds < 0.0 {
+        return None;
+    }
+    Some(minutes * 60_000 + (seconds * 1000.0).round() as i64)
+}
+
+/// Parse LRC text. A line may have several timestamps, and the `[offset:]` tag is applied.
+/// Lines without timestamps and other tags are ignored
+pub fn parse_lrc(text: &str) -> Vec<LyricLine> {
+    let mut offset = 0;
+    let mut lines = Vec::new();
+    for raw_line in text.lines() {
+        let mut rest = raw_line.trim();
+        let mut stamps = Vec::new();
+        while let Some(tag) = rest.strip_prefix('[') {
+            let Some((tag, after)) = tag.split_once(']') else {
+                break;
+            };
+            if let Some(value) = tag.strip_prefix("offset:") {
+                offset = value.trim().parse::<i64>().unwrap_or(0);
+            } else if let Some(stamp) = parse_timestamp(tag) {
+                stamps.push(stamp);
+            }
+            rest = after.trim_start();
+        }
+        for stamp in stamps {
+            lines.push(LyricLine {
+                start_ms: stamp,
+                text: rest.trim().to_owned(),
+            });
+        }
+    }
+    // A positive LRC offset shows the lyrics earlier
+    for line in lines.iter_mut() {
+        line.start_ms -= offset;
+    }
+    lines.sort();
+    lines
+}
+
+fn srt_time(ms: i64) -> String {
+    let ms = ms.max(0);
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        ms / 3_600_000,
+        (ms / 60_000) % 60,
+        (ms / 1000) % 60,
+        ms % 1000
+    )
+}
+
+/// Convert the lyrics to SRT, shifted by `offset_ms`. Each line is shown until the next one starts
+/// and empty lines only end the previous line
+pub fn to_srt(lines: &[LyricLine], offset_ms: i64) -> String {
+    let mut srt = String::new();
+    let mut index = 1;
+    for (i, line) in lines.iter().enumerate() {
+        if line.text.is_empty() {
+            continue;
+        }
+        let start = line.start_ms + offset_ms;
+        let end = lines
+            .get(i + 1)
+            .map(|next| next.start_ms + offset_ms)
+            .unwrap_or(start + LAST_LINE_MS);
+        if end <= 0 {
+            continue;
+        }
+        srt.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            index,
+            srt_time(start),
+            srt_time(end),
+            line.text
+        ));
+        index += 1;
+    }
+    srt
+}
+
+/// Read the synced lyrics (SYLT) frame of an ID3v2.3 or ID3v2.4 tag at the start of the file.
+/// Only frames with millisecond timestamps are used
+fn id3_synced_lyrics(audio_path: &str) -> Option<Vec<LyricLine>> {
+    let mut file = fs::File::open(audio_path).ok()?;
+    let mut header = [0; 10];
+    file.read_exact(&mut header).ok()?;
+    let version = header[3];
+    if &header[..3] != b"ID3" || !(3..=4).contains(&version) {
+        return None;
+    }
+    let mut tag = vec![0; syncsafe(&header[6..10])];
+    file.read_exact(&mut tag).ok()?;
+    if header[5] & 0x80 != 0 {
+        // Unsynchronisation put a 0 after every 0xFF
+        let mut previous = 0;
+        tag.retain(|&byte| {
+            let keep = !(previous == 0xFF && byte == 0);
+            previous = byte;
+            keep
+        });
+    }
+    let mut pos = 0;
+    if header[5] & 0x40 != 0 {
+        let size = tag.get(..4)?;
+        pos = match version {
+            4 => syncsafe(size),
+            _ => 4 + u32::from_be_bytes(size.try_into().ok()?) as usize,
+        };
+    }
+    while let Some(frame_header) = tag.get(pos..pos + 10) {
+        if frame_header[0] == 0 {
+            break;
+        }
+        let size = match version {
+            4 => syncsafe(&frame_header[4..8]),
+            _ => u32::from_be_bytes(frame_header[4..8].try_into().ok()?) as usize,
+        };
+        let body = tag.get(pos + 10..pos + 10 + size)?;
+        if &frame_header[..4] == b"SYLT" {
+            if let Some(lines) = parse_sylt(body).filter(|lines| !lines.is_empty()) {
+                return Some(lines);
+            }
+        }
+        pos += 10 + size;
+    }
+    None
+}
+
+/// Size stored in 7 bits per byte
+fn syncsafe(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .fold(0, |size, &byte| (size << 7) | (byte & 0x7F) as usize)
+}
+
+/// Parse the body of a SYLT frame: encoding, language, timestamp format, content type and
+/// descriptor, then the texts, each followed by its timestamp
+fn parse_sylt(body: &[u8]) -> Option<Vec<LyricLine>> {
+    let encoding = *body.first()?;
+    // 2 means milliseconds, 1 MPEG frames
+    if *body.get(4)? != 2 {
+        return None;
+    }
+    let (_, mut rest) = split_id3_text(body.get(6..)?, encoding)?;
+    let mut lines = Vec::new();
+    while !rest.is_empty() {
+        let (text, after) = split_id3_text(rest, encoding)?;
+        let stamp = after.get(..4)?;
+        lines.push(LyricLine {
+            start_ms: u32::from_be_bytes(stamp.try_into().ok()?) as i64,
+            text: text.trim().to_owned(),
+        });
+        rest = &after[4..];
+    }
+    lines.sort();
+    Some(lines)
+}
+
+/// Split a terminated ID3 string off the front of `bytes`. Encodings 1 and 2 are UTF-16 and end
+/// with two zero bytes, 0 is Latin-1 and 3 is UTF-8
+fn split_id3_text(bytes: &[u8], encoding: u8) -> Option<(String, &[u8])> {
+    if encoding == 1 || encoding == 2 {
+        let end = (0..bytes.len() / 2).find(|i| bytes[2 * i] == 0 && bytes[2 * i + 1] == 0)?;
+        let mut text = &bytes[..2 * end];
+        let mut big_endian = encoding == 2;
+        match text {
+            [0xFE, 0xFF, ..] => (big_endian, text) = (true, &text[2..]),
+            [0xFF, 0xFE, ..] => (big_endian, text) = (false, &text[2..]),
+            _ => {}
+        }
+        let units = text.chunks_exact(2).map(|pair| match big_endian {
+            true => u16::from_be_bytes([pair[0], pair[1]]),
+            false => u16::from_le_bytes([pair[0], pair[1]]),
+        });
+        let text = char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect();
+        return Some((text, &bytes[2 * end + 2..]));
+    }
+    let end = bytes.iter().position(|&byte| byte == 0)?;
+    let text = match encoding {
+        0 => bytes[..end].iter().map(|&byte| byte as char).collect(),
+        _ => String::from_utf8_lossy(&bytes[..end]).to_string(),
+    };
+    Some((text, &bytes[end + 1..]))
+}
+
+/// Find synced lyrics for the audio file: a `.lrc` file with the same name next to it, a synced
+/// lyrics frame of an ID3 tag or an embedded lyrics tag that has LRC timestamps
+///
+/// Returns why there are none otherwise, so that unsynced lyrics aren't dropped silently
+pub fn find_lyrics(audio_path: &str) -> Result<Vec<LyricLine>, String> {
+    let file_name = Path::new(audio_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut unsynced = false;
+    let sidecar = Path::new(audio_path).with_extension("lrc");
+    if let Ok(text) = fs::read_to_string(sidecar) {
+        let lines = parse_lrc(&text);
+        if !lines.is_empty() {
+            return Ok(lines);
+        }
+        unsynced |= !text.trim().is_empty();
+    }
+    if let Some(lines) = id3_synced_lyrics(audio_path) {
+        return Ok(lines);
+    }
+    let tags = probe::probe(audio_path)
+        .map(|info| info.tags)
+        .unwrap_or_default();
+    for (key, text) in tags.iter() {
+        if !key.starts_with("lyrics") && !key.ends_with("lyrics") {
+            continue;
+        }
+        let lines = parse_lrc(text);
+        if !lines.is_empty() {
+            return Ok(lines);
+        }
+        unsynced |= !text.trim().is_empty();
+    }
+    Err(if unsynced {
+        format!("No synced lyrics for {}, only unsynced ones", file_name)
+    } else {
+        format!("No lyrics for {}", file_name)
+    })
+}
+
+/// Write an SRT file for the synced lyrics of the audio file into `cache_dir`
+///
+/// Returns the path of the subtitle file, or why there is none
+pub fn write_subtitle(
+    audio_path: &str,
+    offset_ms: i64,
+    cache_dir: &str,
+    video_name: &str,
+) -> Result<PathBuf, String> {
+    let lines = find_lyrics(audio_path)?;
+    let lyrics_dir = Path::new(cache_dir).join("lyrics");
+    let file_name = Path::new(video_name).with_extension("srt");
+    let srt_path = lyrics_dir.join(file_name.file_name().unwrap_or_default());
+    fs::create_dir_all(&lyrics_dir)
+        .and_then(|_| fs::write(&srt_path, to_srt(&lines, offset_ms)))
+        .map_err(|e| format!("Couldn't write {}: {}", srt_path.display(), e))?;
+    Ok(srt_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    const LRC: &str = "[ti:Blueming]\n[offset:500]\n[00:01.50][00:10.00]Line one\n[00:05.25]Line two\n[00:08.00]\n";
+
+    #[test]
+    fn test_parse_lrc() {
+        let lines = parse_lrc(LRC);
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0].start_ms, 1000);
+        assert_eq!(lines[0].text, "Line one");
+        assert_eq!(lines[1].start_ms, 4750);
+        assert_eq!(lines[3].start_ms, 9500);
+    }
+
+    #[test]
+    fn test_to_srt() {
+        let srt = to_srt(&parse_lrc(LRC), 250);
+        let expected = "1\n00:00:01,250 --> 00:00:05,000\nLine one\n\n\
+                        2\n00:00:05,000 --> 00:00:07,750\nLine two\n\n\
+                        3\n00:00:09,750 --> 00:00:14,750\nLine one\n\n";
+        assert_eq!(srt, expected);
+    }
+
+    #[test]
+    fn test_write_subtitle_from_sidecar() {
+        let temp_dir = TempDir::new("test_write_subtitle").unwrap();
+        let audio_path = temp_dir.path().join("song.flac");
+        fs::write(&audio_path, "").unwrap();
+        fs::write(temp_dir.path().join("song.lrc"), LRC).unwrap();
+        let cache_dir = temp_dir.path().join("cache");
+        let srt_path = write_subtitle(
+            audio_path.to_str().unwrap(),
+            0,
+            cache_dir.to_str().unwrap(),
+            "IU - Blueming.mp4",
+        )
+        .unwrap();
+        assert!(srt_path.ends_with("lyrics/IU - Blueming.srt"));
+        assert!(fs::read_to_string(srt_path)
+            .unwrap()
+            .starts_with("1\n00:00:01,000"));
+    }
+
+    #[test]
+    fn test_find_lyrics_in_sylt() {
+        let temp_dir = TempDir::new("test_find_lyrics_in_sylt").unwrap();
+        let mut body = vec![3];
+        body.extend(b"kor\x02\x01Blueming\x00");
+        body.extend(b"Line one\x00\x00\x00\x05\xDC");
+        body.extend(b"\nLine two\x00\x00\x00\x13\x88");
+        let mut tag = b"SYLT".to_vec();
+        tag.extend((body.len() as u32).to_be_bytes());
+        tag.extend([0, 0]);
+        tag.extend(body);
+        let mut file = b"ID3\x03\x00\x00\x00\x00\x00".to_vec();
+        file.push(tag.len() as u8);
+        file.extend(tag);
+        let audio_path = temp_dir.path().join("song.mp3");
+        fs::write(&audio_path, file).unwrap();
+        let lines = find_lyrics(audio_path.to_str().unwrap()).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].start_ms, 1500);
+        assert_eq!(lines[1].text, "Line two");
+    }
+
+    #[test]
+    fn test_find_lyrics_reports_unsynced() {
+        let temp_dir = TempDir::new("test_find_lyrics_reports_unsynced").unwrap();
+        let audio_path = temp_dir.path().join("song.flac");
+        fs::write(&audio_path, "").unwrap();
+        fs::write(temp_dir.path().join("song.lrc"), "Line one\nLine two\n").unwrap();
+        assert_eq!(
+            find_lyrics(audio_path.to_str().unwrap()),
+            Err("No synced lyrics for song.flac, only unsynced ones".to_owned())
+        );
+    }
+}
@@ -1,6 +1,10 @@
-use rust_mvplayer::run;
+use rust_mvplayer::{run, run_integrity};
 
 #[tokio::main(flavor = "multi_thread", worker_threads = 4)]
 async fn main() {
+    let args = std::env::args().collect::<Vec<String>>();
+    if args.get(1).is_some_and(|arg| arg == "integrity") {
+        std::process::exit(run_integrity(&args[2..]));
+    }
     run().await;
 }
@@ -1,18 +1,450 @@
+use super::mpv_ipc::{self, MpvIpc};
+use super::player_command::PlayerCommand;
+use super::player_processes::{self, Signal};
+use super::player_sync::PlayerSync;
+use super::probe;
+use std::collections::HashMap;
+use std::io::{self, Write};
 use std::process::Stdio;
-use std::sync::Arc;
-use tokio::process::Command;
-use tokio::sync;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncReadExt;
+use tokio::process::{Child, ChildStderr, Command};
+use tokio::sync::mpsc::UnboundedSender;
 use tokio::time;
+use tokio_util::sync::CancellationToken;
 
+/// What to play
+/// # Fields
+/// * `audio_path`: The audio file
+/// * `video_path`: The video file
+/// * `audio_args`: Passed to the audio player before the file path
+/// * `video_args`: Passed to the video player before the file path
+/// * `sync_offset_ms`: How much later the audio should play than the video, in milliseconds
+/// * `start`, `end`: Part of the video to play, in seconds
+/// * `volume`: Volume in percent that levels the audio, for command templates
+/// * `title`, `artist`: From the video name, for command templates
+/// * `video_cmd`, `audio_cmd`: Replace the commands of the player, from a player profile
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlayRequest {
+    pub audio_path: String,
+    pub video_path: String,
+    pub audio_args: Vec<String>,
+    pub video_args: Vec<String>,
+    pub sync_offset_ms: i64,
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+    pub volume: Option<f64>,
+    pub title: String,
+    pub artist: String,
+    pub video_cmd: Option<String>,
+    pub audio_cmd: Option<String>,
+}
+
+impl PlayRequest {
+    /// Values of the command template placeholders
+    pub fn template_values(&self) -> HashMap<&'static str, String> {
+        let seconds = |value: Option<f64>| value.map(|v| format!("{:.3}", v)).unwrap_or_default();
+        HashMap::from([
+            ("video", self.video_path.to_owned()),
+            ("audio", self.audio_path.to_owned()),
+            (
+                "offset",
+                format!("{:.3}", self.sync_offset_ms as f64 / 1000.0),
+            ),
+            ("start", seconds(self.start)),
+            ("end", seconds(self.end)),
+            (
+                "volume",
+                self.volume.map(|v| format!("{:.0}", v)).unwrap_or_default(),
+            ),
+            ("title", self.title.to_owned()),
+            ("artist", self.artist.to_owned()),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlayerStatus {
+    /// Nothing was played yet or playback was stopped
+    #[default]
+    Idle,
+    Playing,
+    /// The players exited on their own
+    Finished,
+}
+
+/// A change to the running player
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayerControl {
+    TogglePause,
+    /// Seek by seconds from the current position
+    Seek(f64),
+    /// Change the volume by percent
+    Volume(f64),
+}
+
+/// What the running player reports
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlaybackState {
+    pub position: f64,
+    pub duration: Option<f64>,
+    pub paused: bool,
+    pub volume: Option<f64>,
+}
+
+/// Sent when playback of a video ends, with the video path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlayerEvent {
+    /// The players exited on their own, usually at the end of the track
+    Finished(String),
+    /// Playback was stopped or replaced by another request
+    Stopped(String),
+    /// No player could be started or a player exited with an error. The other players were
+    /// stopped
+    Failed { video_path: String, error: String },
+}
+
+/// Plays audio and video. Playing while something is already playing replaces it
+pub trait PlayerBackend {
+    fn play(&mut self, request: PlayRequest);
+    fn stop(&mut self);
+    fn status(&self) -> PlayerStatus;
+    /// Send playback events to `events` from now on
+    fn subscribe(&mut self, events: UnboundedSender<PlayerEvent>);
+
+    /// Backends that can't control a running player return an `Unsupported` error
+    fn control(&mut self, _control: PlayerControl) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "This player can't be controlled",
+        ))
+    }
+
+    fn playback_state(&mut self) -> Option<PlaybackState> {
+        None
+    }
+}
+
+/// The backend named in the config. "mpv" plays both in one mpv started with `video_cmd`,
+/// "synced" runs two mpv processes that are kept in sync, "record" only records what would be
+/// played. The fallbacks are tried in order when a command can't be started or fails right away
+pub fn backend_from_config(
+    name: &str,
+    video_cmd: &str,
+    audio_cmd: &str,
+    drift_threshold_ms: u64,
+    video_fallbacks: &[String],
+    audio_fallbacks: &[String],
+) -> Box<dyn PlayerBackend> {
+    let dual = DualProcessPlayer::new(video_cmd.to_owned(), audio_cmd.to_owned())
+        .with_fallbacks(video_fallbacks.to_vec(), audio_fallbacks.to_vec());
+    match name.trim().to_lowercase().as_str() {
+        "mpv" => {
+            Box::new(MpvPlayer::new(video_cmd.to_owned()).with_fallbacks(video_fallbacks.to_vec()))
+        }
+        "synced" => Box::new(dual.with_sync(drift_threshold_ms)),
+        "record" => Box::<RecordingPlayer>::default(),
+        _ => Box::new(dual),
+    }
+}
+
+fn command_line(
+    cmd: &str,
+    extra_args: Vec<String>,
+    files: Vec<String>,
+    values: &HashMap<&str, String>,
+) -> CommandLine {
+    let cmd = PlayerCommand::parse(cmd);
+    let args = cmd.args(extra_args, files, values);
+    (cmd.program, args)
+}
+
+/// The command followed by the fallbacks. Only the command gets `extra_args`, as they are
+/// options of the backend that other players may not understand
+fn command_lines(
+    cmd: &str,
+    fallbacks: &[String],
+    extra_args: Vec<String>,
+    files: Vec<String>,
+    values: &HashMap<&str, String>,
+) -> Vec<CommandLine> {
+    let mut commands = vec![command_line(cmd, extra_args, files.clone(), values)];
+    commands.extend(
+        fallbacks
+            .iter()
+            .map(|fallback| command_line(fallback, Vec::new(), files.clone(), values)),
+    );
+    commands
+}
+
+/// How much of the end of a failed player's stderr is shown
+const STDERR_EXCERPT_LINES: usize = 3;
+
+/// How soon a player has to exit with an error for the next command to be tried instead. A
+/// fallback is meant for a player that can't play at all, not one that fails later on
+const STARTUP_WINDOW: time::Duration = time::Duration::from_secs(2);
+
+/// A program and its arguments. Players are started from a list of these, the first one that
+/// can be started is used
+pub type CommandLine = (String, Vec<String>);
+
+/// Player processes of the last request and their shared status
+/// # Fields
+/// * `stop_token`: Tells the tasks that wait for the processes of the last request to kill them
+/// * `running`: Number of processes of the last request that are still running
+/// * `status`: Status of the last request, shared with the tasks that wait for its processes.
+///   The token is only cancelled while it is locked, so a task that holds it and sees the token
+///   not cancelled reports for the current request
+/// * `video_path`: Video of the last request, sent with the events
+/// * `events`: Where finished, stopped and failed events are sent
 #[derive(Default)]
-pub struct MediaPlayer {
-    audio_tx: Option<Arc<sync::mpsc::Sender<usize>>>,
-    video_tx: Option<Arc<sync::mpsc::Sender<usize>>>,
+struct Processes {
+    stop_token: CancellationToken,
+    running: Arc<Mutex<usize>>,
+    status: Arc<Mutex<PlayerStatus>>,
+    video_path: String,
+    events: Option<UnboundedSender<PlayerEvent>>,
+}
+
+impl Processes {
+    /// Stop the processes of the last request before starting the ones for `video_path`
+    fn replace(&mut self, video_path: &str) {
+        self.stop();
+        self.stop_token = CancellationToken::new();
+        self.running = Arc::new(Mutex::new(0));
+        self.status = Arc::new(Mutex::new(PlayerStatus::Idle));
+        self.video_path = video_path.to_owned();
+    }
+
+    /// Run the first of the commands that can be started until it exits or is stopped. A player
+    /// that exits with an error within `STARTUP_WINDOW` is replaced by the next command. The
+    /// player gets its own process group so that it and anything it starts can be killed
+    /// together. When none can be started, or the player exits with an error, the other players
+    /// of the request are stopped and a failed event is sent
+    fn spawn(&mut self, commands: Vec<CommandLine>, quiet: bool) {
+        if self.stop_token.is_cancelled() {
+            return;
+        }
+        let mut commands = commands.into_iter();
+        let mut errors = Vec::new();
+        let Some((program, child)) = start_first(&mut commands, quiet, &mut errors) else {
+            let error = if errors.is_empty() {
+                "No player command".to_owned()
+            } else {
+                errors.join("\n")
+            };
+            self.fail(error);
+            return;
+        };
+        *self.running.lock().unwrap() += 1;
+        *self.status.lock().unwrap() = PlayerStatus::Playing;
+        let stop_token = self.stop_token.clone();
+        let running = self.running.clone();
+        let status = self.status.clone();
+        let events = self.events.clone();
+        let video_path = self.video_path.clone();
+        tokio::spawn(async move {
+            let (mut program, mut child) = (program, child);
+            let error = loop {
+                let started = time::Instant::now();
+                let stderr = child
+                    .stderr
+                    .take()
+                    .map(|stderr| tokio::spawn(read_stderr(stderr, quiet)));
+                let pid = child.id();
+                if let Some(pid) = pid {
+                    player_processes::register(pid, &program);
+                }
+                let exit = tokio::select! {
+                    exit = child.wait() => Some(exit),
+                    _ = stop_token.cancelled() => {
+                        let asked = pid.is_some_and(|pid| {
+                            player_processes::signal_group(pid, Signal::Terminate)
+                        });
+                        if !asked
+                            || time::timeout(player_processes::GRACE_PERIOD, child.wait())
+                                .await
+                                .is_err()
+                        {
+                            if let Some(pid) = pid {
+                                player_processes::signal_group(pid, Signal::Kill);
+                            }
+                            let _ = child.kill().await;
+                        }
+                        None
+                    }
+                };
+                if let Some(pid) = pid {
+                    player_processes::unregister(pid);
+                }
+                let Some(exit) = exit else {
+                    return;
+                };
+                let error = match exit {
+                    Ok(exit) if exit.success() => break None,
+                    Ok(exit) => {
+                        // Players that start more processes may keep stderr open
+                        let stderr = match stderr {
+                            Some(stderr) => time::timeout(time::Duration::from_secs(1), stderr)
+                                .await
+                                .ok()
+                                .and_then(|tail| tail.ok())
+                                .unwrap_or_default(),
+                            None => String::new(),
+                        };
+                        failure_message(&program, &exit.to_string(), &stderr)
+                    }
+                    Err(e) => format!("{}: {}", program, e),
+                };
+                if started.elapsed() >= STARTUP_WINDOW || stop_token.is_cancelled() {
+                    break Some(error);
+                }
+                errors.push(error);
+                match start_first(&mut commands, quiet, &mut errors) {
+                    Some(next) => (program, child) = next,
+                    None => break Some(errors.join("\n")),
+                }
+            };
+            let mut status = status.lock().unwrap();
+            if stop_token.is_cancelled() {
+                return;
+            }
+            let mut running = running.lock().unwrap();
+            *running -= 1;
+            let event = match error {
+                Some(error) => {
+                    stop_token.cancel();
+                    *status = PlayerStatus::Idle;
+                    PlayerEvent::Failed { video_path, error }
+                }
+                None if *running == 0 => {
+                    *status = PlayerStatus::Finished;
+                    PlayerEvent::Finished(video_path)
+                }
+                None => return,
+            };
+            if let Some(events) = events {
+                let _ = events.send(event);
+            }
+        });
+    }
+
+    /// Stop the request because one of its players failed
+    fn fail(&mut self, error: String) {
+        let mut status = self.status.lock().unwrap();
+        self.stop_token.cancel();
+        *status = PlayerStatus::Idle;
+        if let Some(events) = &self.events {
+            let _ = events.send(PlayerEvent::Failed {
+                video_path: self.video_path.clone(),
+                error,
+            });
+        }
+    }
+
+    fn stop(&mut self) {
+        let mut status = self.status.lock().unwrap();
+        self.stop_token.cancel();
+        if *status == PlayerStatus::Playing {
+            if let Some(events) = &self.events {
+                let _ = events.send(PlayerEvent::Stopped(self.video_path.clone()));
+            }
+        }
+        *status = PlayerStatus::Idle;
+    }
+
+    fn status(&self) -> PlayerStatus {
+        *self.status.lock().unwrap()
+    }
+}
+
+/// Read the stderr of a player until it is closed and return the end of it. Unless `quiet` it
+/// is passed on to the terminal
+async fn read_stderr(mut stderr: ChildStderr, quiet: bool) -> String {
+    const KEEP: usize = 4096;
+    let mut tail = Vec::new();
+    let mut buf = [0; 1024];
+    while let Ok(read) = stderr.read(&mut buf).await {
+        if read == 0 {
+            break;
+        }
+        if !quiet {
+            let mut terminal = io::stderr();
+            terminal.write_all(&buf[..read]).ok();
+            terminal.flush().ok();
+        }
+        tail.extend_from_slice(&buf[..read]);
+        if tail.len() > KEEP {
+            tail.drain(..tail.len() - KEEP);
+        }
+    }
+    String::from_utf8_lossy(&tail).to_string()
+}
+
+/// Start the first of the commands that can be started. Why the others couldn't is added to
+/// `errors`
+fn start_first(
+    commands: &mut impl Iterator<Item = CommandLine>,
+    quiet: bool,
+    errors: &mut Vec<String>,
+) -> Option<(String, Child)> {
+    for (program, args) in commands {
+        let mut command = Command::new(&program);
+        command.args(args).stderr(Stdio::piped()).kill_on_drop(true);
+        #[cfg(unix)]
+        command.process_group(0);
+        if quiet {
+            command.stdout(Stdio::null());
+        }
+        match command.spawn() {
+            Ok(child) => return Some((program, child)),
+            Err(e) => errors.push(format!("{}: {}", program, e)),
+        }
+    }
+    None
+}
+
+/// What is shown when a player exits with an error: the exit status and the last lines of its
+/// stderr
+fn failure_message(program: &str, exit: &str, stderr: &str) -> String {
+    let lines = stderr
+        .split(['\n', '\r'])
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    let excerpt = lines[lines.len().saturating_sub(STDERR_EXCERPT_LINES)..]
+        .iter()
+        .map(|line| line.chars().take(200).collect::<String>())
+        .collect::<Vec<_>>();
+    if excerpt.is_empty() {
+        format!("{} exited with {}", program, exit)
+    } else {
+        format!("{} exited with {}:\n{}", program, exit, excerpt.join("\n"))
+    }
+}
+
+/// Plays the audio and the video in two separate player processes
+/// # Fields
+/// * `video_cmd`: Video player command, see `PlayerCommand`
+/// * `audio_cmd`: Audio player command, see `PlayerCommand`
+/// * `video_fallbacks`, `audio_fallbacks`: Commands tried in order when a player can't be started
+///   or fails right away
+/// * `drift_threshold_ms`: When set both players are mpv, they are started together and drift
+///   above this is corrected
+/// * `synced`: The audio and video player of the last request and the flag that stops syncing
+#[derive(Default)]
+pub struct DualProcessPlayer {
     video_cmd: String,
     audio_cmd: String,
+    video_fallbacks: Vec<String>,
+    audio_fallbacks: Vec<String>,
+    processes: Processes,
+    drift_threshold_ms: Option<u64>,
+    synced: Option<(MpvIpc, MpvIpc, Arc<AtomicBool>)>,
 }
 
-impl MediaPlayer {
+impl DualProcessPlayer {
     pub fn new(video_cmd: String, audio_cmd: String) -> Self {
         Self {
             video_cmd,
@@ -20,50 +452,410 @@ impl MediaPlayer {
             ..Default::default()
         }
     }
-    pub async fn play_media(&mut self, audio_path: String, video_path: String) {
-        if self.audio_tx.is_some() {
-            let _ = self.audio_tx.as_ref().unwrap().send(1).await;
+
+    pub fn with_fallbacks(
+        mut self,
+        video_fallbacks: Vec<String>,
+        audio_fallbacks: Vec<String>,
+    ) -> Self {
+        self.video_fallbacks = video_fallbacks;
+        self.audio_fallbacks = audio_fallbacks;
+        self
+    }
+
+    /// Start both players paused and unpause them together, then correct drift above the
+    /// threshold. Both commands must be mpv
+    pub fn with_sync(mut self, drift_threshold_ms: u64) -> Self {
+        self.drift_threshold_ms = Some(drift_threshold_ms);
+        self
+    }
+
+    fn stop_sync(&mut self) {
+        if let Some((mut audio, mut video, stop)) = self.synced.take() {
+            stop.store(true, Ordering::Relaxed);
+            audio.quit().ok();
+            video.quit().ok();
         }
-        if self.video_tx.is_some() {
-            let _ = self.video_tx.as_ref().unwrap().send(1).await;
+    }
+}
+
+impl PlayerBackend for DualProcessPlayer {
+    fn play(&mut self, request: PlayRequest) {
+        // Cancelled first, so the players quitting are not reported as finished
+        self.processes.replace(&request.video_path);
+        self.stop_sync();
+        let values = request.template_values();
+        let mut audio_args = request.audio_args;
+        let mut video_args = request.video_args;
+        if let Some(threshold) = self.drift_threshold_ms {
+            let audio = MpvIpc::new(&mpv_ipc::socket_path("audio"));
+            let video = MpvIpc::new(&mpv_ipc::socket_path("video"));
+            audio_args.extend(["--pause".to_owned(), audio.server_arg()]);
+            video_args.extend(["--pause".to_owned(), video.server_arg()]);
+            let stop = Arc::new(AtomicBool::new(false));
+            PlayerSync {
+                audio: audio.clone(),
+                video: video.clone(),
+                offset: request.sync_offset_ms as f64 / 1000.0,
+                threshold: threshold as f64 / 1000.0,
+                stop: stop.clone(),
+            }
+            .spawn();
+            self.synced = Some((audio, video, stop));
         }
-        let (audio_tx, mut audio_rx) = sync::mpsc::channel::<usize>(1);
-        let (video_tx, mut video_rx) = sync::mpsc::channel::<usize>(1);
-        self.audio_tx = Some(Arc::new(audio_tx));
-        self.video_tx = Some(Arc::new(video_tx));
-        let audio_cmd = self.audio_cmd.clone();
-        let video_cmd = self.video_cmd.clone();
-        tokio::spawn(async move {
-            let audio_split = audio_cmd.split(',');
-            let mut child = Command::new(audio_split.clone().next().unwrap())
-                .args(audio_split.skip(1))
-                .arg(audio_path)
-                .spawn()
-                .expect("failed to spawn video player task");
-            while child.try_wait().unwrap().is_none() {
-                time::sleep(time::Duration::from_millis(1000)).await;
-                if audio_rx.try_recv().is_ok() {
-                    let _ = child.kill().await;
-                    break;
-                }
+        let audio_cmds = command_lines(
+            request.audio_cmd.as_ref().unwrap_or(&self.audio_cmd),
+            &self.audio_fallbacks,
+            audio_args,
+            vec![request.audio_path],
+            &values,
+        );
+        let video_cmds = command_lines(
+            request.video_cmd.as_ref().unwrap_or(&self.video_cmd),
+            &self.video_fallbacks,
+            video_args,
+            vec![request.video_path],
+            &values,
+        );
+        self.processes.spawn(audio_cmds, false);
+        self.processes.spawn(video_cmds, true);
+    }
+
+    fn stop(&mut self) {
+        self.processes.stop();
+        self.stop_sync();
+    }
+
+    fn status(&self) -> PlayerStatus {
+        self.processes.status()
+    }
+
+    fn subscribe(&mut self, events: UnboundedSender<PlayerEvent>) {
+        self.processes.events = Some(events);
+    }
+
+    /// Only synced players can be controlled. Both are paused and seeked together
+    fn control(&mut self, control: PlayerControl) -> io::Result<()> {
+        let Some((audio, video, _)) = self.synced.as_mut() else {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Only synced players can be controlled",
+            ));
+        };
+        match control {
+            PlayerControl::TogglePause => {
+                let paused = !video.is_paused()?;
+                video.set_paused(paused)?;
+                audio.set_paused(paused)
             }
-        });
-        tokio::spawn(async move {
-            let video_split = video_cmd.split(',');
-            let mut child = Command::new(video_split.clone().next().unwrap())
-                .args(video_split.skip(1))
-                .arg(video_path)
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .spawn()
-                .expect("failed to spawn video player task");
-            while child.try_wait().unwrap().is_none() {
-                time::sleep(time::Duration::from_millis(1000)).await;
-                if video_rx.try_recv().is_ok() {
-                    let _ = child.kill().await;
-                    break;
-                }
+            PlayerControl::Seek(seconds) => {
+                video.seek_relative(seconds)?;
+                audio.seek_relative(seconds)
             }
-        });
+            PlayerControl::Volume(amount) => audio.add_volume(amount),
+        }
+    }
+
+    fn playback_state(&mut self) -> Option<PlaybackState> {
+        let (audio, video, _) = self.synced.as_mut()?;
+        Some(PlaybackState {
+            position: video.position().ok()?,
+            duration: video.duration().ok(),
+            paused: video.is_paused().ok()?,
+            volume: audio.volume().ok(),
+        })
+    }
+}
+
+/// Plays the video in a single mpv with the audio as an external audio track, so both stay in
+/// sync and there is one window to control
+/// # Fields
+/// * `mpv_cmd`: mpv command, see `PlayerCommand`. A template places the audio with
+///   `--audio-file={audio}` itself
+/// * `fallbacks`: Commands tried in order when mpv can't be started or fails right away. They get
+///   the video, and the audio only through `{audio}` in a template
+/// * `ipc`: Controls the running mpv
+pub struct MpvPlayer {
+    mpv_cmd: String,
+    fallbacks: Vec<String>,
+    processes: Processes,
+    ipc: Option<MpvIpc>,
+}
+
+impl MpvPlayer {
+    pub fn new(mpv_cmd: String) -> Self {
+        Self {
+            mpv_cmd,
+            fallbacks: Vec::new(),
+            processes: Processes::default(),
+            ipc: None,
+        }
+    }
+
+    pub fn with_fallbacks(mut self, fallbacks: Vec<String>) -> Self {
+        self.fallbacks = fallbacks;
+        self
+    }
+
+    /// mpv options for the request. `embedded_audio` is the number of audio tracks in the
+    /// video, the external track comes after them
+    pub fn args(request: &PlayRequest, embedded_audio: Option<usize>) -> Vec<String> {
+        let mut args = request.video_args.clone();
+        args.extend(request.audio_args.iter().cloned());
+        // Without a probe mpv picks the track, which is usually the external one
+        if let Some(embedded_audio) = embedded_audio {
+            args.push(format!("--aid={}", embedded_audio + 1));
+        }
+        if request.sync_offset_ms != 0 {
+            args.push(format!(
+                "--audio-delay={:.3}",
+                request.sync_offset_ms as f64 / 1000.0
+            ));
+        }
+        args
+    }
+
+    /// The audio track and video arguments when the command is not a template
+    pub fn file_args(request: &PlayRequest) -> Vec<String> {
+        vec![
+            format!("--audio-file={}", request.audio_path),
+            request.video_path.to_owned(),
+        ]
+    }
+
+    fn quit(&mut self) {
+        if let Some(mut ipc) = self.ipc.take() {
+            ipc.quit().ok();
+        }
+    }
+}
+
+impl PlayerBackend for MpvPlayer {
+    fn play(&mut self, request: PlayRequest) {
+        self.processes.replace(&request.video_path);
+        self.quit();
+        let embedded_audio = probe::probe(&request.video_path).map(|info| info.audio_streams);
+        let ipc = MpvIpc::new(&mpv_ipc::socket_path("mpv"));
+        let mut args = vec![ipc.server_arg()];
+        args.extend(Self::args(&request, embedded_audio));
+        let values = request.template_values();
+        let mpv_cmd = request.video_cmd.as_ref().unwrap_or(&self.mpv_cmd);
+        let mut commands = vec![command_line(
+            mpv_cmd,
+            args,
+            Self::file_args(&request),
+            &values,
+        )];
+        commands.extend(self.fallbacks.iter().map(|fallback| {
+            command_line(
+                fallback,
+                Vec::new(),
+                vec![request.video_path.to_owned()],
+                &values,
+            )
+        }));
+        self.processes.spawn(commands, true);
+        self.ipc = Some(ipc);
+    }
+
+    /// Ask mpv to quit, it is killed if it doesn't. The task is cancelled first so that mpv
+    /// exiting is reported as stopped
+    fn stop(&mut self) {
+        self.processes.stop();
+        self.quit();
+    }
+
+    fn status(&self) -> PlayerStatus {
+        self.processes.status()
+    }
+
+    fn subscribe(&mut self, events: UnboundedSender<PlayerEvent>) {
+        self.processes.events = Some(events);
+    }
+
+    fn control(&mut self, control: PlayerControl) -> io::Result<()> {
+        let Some(ipc) = self.ipc.as_mut() else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "Nothing is playing",
+            ));
+        };
+        match control {
+            PlayerControl::TogglePause => ipc.toggle_pause(),
+            PlayerControl::Seek(seconds) => ipc.seek_relative(seconds),
+            PlayerControl::Volume(amount) => ipc.add_volume(amount),
+        }
+    }
+
+    fn playback_state(&mut self) -> Option<PlaybackState> {
+        let ipc = self.ipc.as_mut()?;
+        Some(PlaybackState {
+            position: ipc.position().ok()?,
+            duration: ipc.duration().ok(),
+            paused: ipc.is_paused().ok()?,
+            volume: ipc.volume().ok(),
+        })
+    }
+}
+
+/// Records the requests instead of playing them. Clones share their state, so a clone can read
+/// the requests and finish playback after the player was handed over
+#[derive(Debug, Clone, Default)]
+pub struct RecordingPlayer {
+    pub requests: Arc<Mutex<Vec<PlayRequest>>>,
+    status: Arc<Mutex<PlayerStatus>>,
+    events: Arc<Mutex<Option<UnboundedSender<PlayerEvent>>>>,
+}
+
+impl RecordingPlayer {
+    /// Act as if the last request played to the end
+    pub fn finish(&self) {
+        *self.status.lock().unwrap() = PlayerStatus::Finished;
+        self.send(PlayerEvent::Finished);
+    }
+
+    fn send(&self, event: fn(String) -> PlayerEvent) {
+        let video_path = match self.requests.lock().unwrap().last() {
+            Some(request) => request.video_path.clone(),
+            None => return,
+        };
+        if let Some(events) = self.events.lock().unwrap().as_ref() {
+            let _ = events.send(event(video_path));
+        }
+    }
+}
+
+impl PlayerBackend for RecordingPlayer {
+    fn play(&mut self, request: PlayRequest) {
+        self.stop();
+        self.requests.lock().unwrap().push(request);
+        *self.status.lock().unwrap() = PlayerStatus::Playing;
+    }
+
+    fn stop(&mut self) {
+        if self.status() == PlayerStatus::Playing {
+            self.send(PlayerEvent::Stopped);
+        }
+        *self.status.lock().unwrap() = PlayerStatus::Idle;
+    }
+
+    fn status(&self) -> PlayerStatus {
+        *self.status.lock().unwrap()
+    }
+
+    fn subscribe(&mut self, events: UnboundedSender<PlayerEvent>) {
+        *self.events.lock().unwrap() = Some(events);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    #[test]
+    fn test_failure_message() {
+        assert_eq!(
+            failure_message("mpv", "exit status: 2", "a\nb\r\nc\n\nd\n"),
+            "mpv exited with exit status: 2:\nb\nc\nd"
+        );
+        assert_eq!(
+            failure_message("mpv", "exit status: 2", ""),
+            "mpv exited with exit status: 2"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_old_request_does_not_touch_new_status() {
+        let mut processes = Processes::default();
+        processes.replace("a.mp4");
+        processes.spawn(vec![("true".to_owned(), Vec::new())], true);
+        processes.replace("b.mp4");
+        let sleep = vec!["5".to_owned()];
+        processes.spawn(vec![("sleep".to_owned(), sleep)], true);
+        time::sleep(time::Duration::from_millis(300)).await;
+        assert_eq!(processes.status(), PlayerStatus::Playing);
+        processes.stop();
+        assert_eq!(processes.status(), PlayerStatus::Idle);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_spawn_fallbacks_and_failures() {
+        let (events, mut received) = unbounded_channel();
+        let mut processes = Processes {
+            events: Some(events),
+            ..Default::default()
+        };
+        processes.replace("a.mp4");
+        processes.spawn(
+            vec![
+                ("./missing-player".to_owned(), Vec::new()),
+                ("true".to_owned(), Vec::new()),
+            ],
+            true,
+        );
+        assert_eq!(
+            received.recv().await,
+            Some(PlayerEvent::Finished("a.mp4".to_owned()))
+        );
+        processes.replace("b.mp4");
+        let script = "echo boom >&2; exit 3".to_owned();
+        processes.spawn(vec![("sh".to_owned(), vec!["-c".to_owned(), script])], true);
+        assert_eq!(
+            received.recv().await,
+            Some(PlayerEvent::Failed {
+                video_path: "b.mp4".to_owned(),
+                error: "sh exited with exit status: 3:\nboom".to_owned(),
+            })
+        );
+        processes.replace("c.mp4");
+        processes.spawn(
+            vec![
+                ("false".to_owned(), Vec::new()),
+                ("true".to_owned(), Vec::new()),
+            ],
+            true,
+        );
+        assert_eq!(
+            received.recv().await,
+            Some(PlayerEvent::Finished("c.mp4".to_owned()))
+        );
+        processes.replace("d.mp4");
+        processes.spawn(vec![("./missing-player".to_owned(), Vec::new())], true);
+        assert_eq!(processes.status(), PlayerStatus::Idle);
+        match received.try_recv() {
+            Ok(PlayerEvent::Failed { video_path, error }) => {
+                assert_eq!(video_path, "d.mp4");
+                assert!(error.starts_with("./missing-player: "));
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn test_mpv_args() {
+        let request = PlayRequest {
+            audio_path: "audio/1.flac".to_owned(),
+            video_path: "video/IU - Blueming.mp4".to_owned(),
+            audio_args: vec!["--af=lavfi=[volume=-3.50dB]".to_owned()],
+            video_args: vec!["--sub-file=1.srt".to_owned()],
+            sync_offset_ms: -250,
+            ..Default::default()
+        };
+        assert_eq!(
+            MpvPlayer::args(&request, Some(1)),
+            vec![
+                "--sub-file=1.srt",
+                "--af=lavfi=[volume=-3.50dB]",
+                "--aid=2",
+                "--audio-delay=-0.250",
+            ]
+        );
+        assert_eq!(
+            MpvPlayer::file_args(&request),
+            vec!["--audio-file=audio/1.flac", "video/IU - Blueming.mp4"]
+        );
     }
 }
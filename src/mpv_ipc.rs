@@ -0,0 +1,212 @@
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// How long to wait for mpv to answer a command
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Talks to a running mpv over the JSON IPC socket it was started with
+/// `--input-ipc-server=<socket_path>`. mpv removes the socket when it exits
+/// # Fields
+/// * `socket_path`: Path of the Unix socket
+/// * `next_id`: Used to match responses to requests, mpv sends events on the same socket
+#[derive(Debug, Clone)]
+pub struct MpvIpc {
+    socket_path: PathBuf,
+    next_id: u64,
+}
+
+static SOCKET_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Unique socket path for a player. A new path is used for every player so that a player that
+/// is still exiting is never mistaken for the new one
+pub fn socket_path(name: &str) -> PathBuf {
+    let count = SOCKET_COUNT.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!(
+        "mvplayer-{}-{}-{}.sock",
+        std::process::id(),
+        name,
+        count
+    ))
+}
+
+impl MpvIpc {
+    pub fn new(socket_path: &Path) -> Self {
+        Self {
+            socket_path: socket_path.to_owned(),
+            next_id: 1,
+        }
+    }
+
+    /// The mpv argument that creates the socket
+    pub fn server_arg(&self) -> String {
+        format!("--input-ipc-server={}", self.socket_path.display())
+    }
+
+    /// Whether mpv created the socket yet
+    pub fn is_ready(&self) -> bool {
+        self.socket_path.exists()
+    }
+
+    /// Run a command and return its data
+    pub fn command(&mut self, command: Value) -> io::Result<Value> {
+        let request_id = self.next_id;
+        self.next_id += 1;
+        let mut stream = connect(&self.socket_path)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        let request = json!({ "command": command, "request_id": request_id });
+        stream.write_all(format!("{}\n", request).as_bytes())?;
+        for line in BufReader::new(stream).lines() {
+            if let Some(data) = parse_response(&line?, request_id) {
+                return data;
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "mpv closed the socket",
+        ))
+    }
+
+    pub fn get_property(&mut self, name: &str) -> io::Result<Value> {
+        self.command(json!(["get_property", name]))
+    }
+
+    pub fn set_property(&mut self, name: &str, value: Value) -> io::Result<()> {
+        self.command(json!(["set_property", name, value]))
+            .map(|_| ())
+    }
+
+    pub fn set_paused(&mut self, paused: bool) -> io::Result<()> {
+        self.set_property("pause", json!(paused))
+    }
+
+    pub fn toggle_pause(&mut self) -> io::Result<()> {
+        self.command(json!(["cycle", "pause"])).map(|_| ())
+    }
+
+    /// Seek by `seconds` from the current position
+    pub fn seek_relative(&mut self, seconds: f64) -> io::Result<()> {
+        self.command(json!(["seek", seconds, "relative"]))
+            .map(|_| ())
+    }
+
+    /// Seek to `seconds` from the start
+    pub fn seek_absolute(&mut self, seconds: f64) -> io::Result<()> {
+        self.command(json!(["seek", seconds, "absolute"]))
+            .map(|_| ())
+    }
+
+    /// Change the volume by `amount` percent
+    pub fn add_volume(&mut self, amount: f64) -> io::Result<()> {
+        self.command(json!(["add", "volume", amount])).map(|_| ())
+    }
+
+    /// Ask mpv to exit
+    pub fn quit(&mut self) -> io::Result<()> {
+        self.command(json!(["quit"])).map(|_| ())
+    }
+
+    /// Seconds from the start of the file
+    pub fn position(&mut self) -> io::Result<f64> {
+        as_f64(self.get_property("time-pos")?)
+    }
+
+    pub fn duration(&mut self) -> io::Result<f64> {
+        as_f64(self.get_property("duration")?)
+    }
+
+    pub fn volume(&mut self) -> io::Result<f64> {
+        as_f64(self.get_property("volume")?)
+    }
+
+    pub fn is_paused(&mut self) -> io::Result<bool> {
+        self.get_property("pause")?
+            .as_bool()
+            .ok_or_else(|| invalid_data("pause is not a bool"))
+    }
+}
+
+#[cfg(unix)]
+fn connect(socket_path: &Path) -> io::Result<std::os::unix::net::UnixStream> {
+    std::os::unix::net::UnixStream::connect(socket_path)
+}
+
+#[cfg(not(unix))]
+fn connect(_socket_path: &Path) -> io::Result<std::fs::File> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "mpv IPC needs Unix sockets",
+    ))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
+
+fn as_f64(value: Value) -> io::Result<f64> {
+    value
+        .as_f64()
+        .ok_or_else(|| invalid_data("property is not a number"))
+}
+
+/// The data of the response to `request_id`, or None when the line is an event or the response
+/// to another request
+fn parse_response(line: &str, request_id: u64) -> Option<io::Result<Value>> {
+    let value = serde_json::from_str::<Value>(line).ok()?;
+    if value["request_id"].as_u64() != Some(request_id) {
+        return None;
+    }
+    Some(match value["error"].as_str() {
+        Some("success") => Ok(value["data"].clone()),
+        Some(error) => Err(io::Error::other(error.to_owned())),
+        None => Err(invalid_data("response without an error field")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        assert!(parse_response(r#"{"event":"pause"}"#, 1).is_none());
+        assert!(parse_response(r#"{"data":1.5,"request_id":2,"error":"success"}"#, 1).is_none());
+        let data = parse_response(r#"{"data":1.5,"request_id":1,"error":"success"}"#, 1);
+        assert_eq!(data.unwrap().unwrap(), json!(1.5));
+        let error = parse_response(r#"{"request_id":1,"error":"property unavailable"}"#, 1);
+        assert_eq!(
+            error.unwrap().unwrap_err().to_string(),
+            "property unavailable"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_over_socket() {
+        use std::os::unix::net::UnixListener;
+        let temp_dir = tempdir::TempDir::new("test_mpv_ipc").unwrap();
+        let path = temp_dir.path().join("mpv.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = String::new();
+            reader.read_line(&mut request).unwrap();
+            let mut stream = stream;
+            stream
+                .write_all(b"{\"event\":\"playback-restart\"}\n")
+                .unwrap();
+            stream
+                .write_all(b"{\"data\":12.5,\"request_id\":1,\"error\":\"success\"}\n")
+                .unwrap();
+            request
+        });
+        let mut ipc = MpvIpc::new(&path);
+        assert_eq!(ipc.position().unwrap(), 12.5);
+        let request = serde_json::from_str::<Value>(&server.join().unwrap()).unwrap();
+        assert_eq!(request["command"], json!(["get_property", "time-pos"]));
+    }
+}
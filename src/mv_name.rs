@@ -0,0 +1,301 @@
+use super::release_date::ReleaseDate;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+/// The parts of an MV file name in the usual "Artist - Title (Version).ext" form. A date prefix
+/// such as "191118 " and a date suffix such as " [2019-11-18]", as the default rename template
+/// writes it, are not part of the artist or title
+/// # Fields
+/// * `artist`: The canonical artist after applying the alias table
+/// * `raw_artist`: The artist as it appears in the file name
+/// * `title`: The title without the version
+/// * `version`: The last bracketed part of the title, for example "Live" or "4K"
+/// * `date`: The date prefix or suffix
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedName {
+    pub artist: String,
+    pub raw_artist: String,
+    pub title: String,
+    pub version: Option<String>,
+    pub date: Option<ReleaseDate>,
+}
+
+impl ParsedName {
+    pub fn parse(video_name: &str, aliases: &AliasTable) -> Self {
+        let file_name = Path::new(video_name)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or(video_name);
+        let (prefix_date, file_name) = split_date_prefix(file_name);
+        let (file_name, suffix_date) = split_date_suffix(file_name);
+        let (raw_artist, rest) = match file_name.split_once(" - ") {
+            Some((artist, rest)) => (artist.trim(), rest.trim()),
+            None => ("", file_name.trim()),
+        };
+        let (title, version) = split_version(rest);
+        Self {
+            artist: aliases.canonical(raw_artist),
+            raw_artist: raw_artist.to_owned(),
+            title,
+            version,
+            date: prefix_date.or(suffix_date),
+        }
+    }
+}
+
+/// Split off a `YYMMDD` or `YYYY-MM-DD` prefix, see `ReleaseDate::from_file_name`
+fn split_date_prefix(file_name: &str) -> (Option<ReleaseDate>, &str) {
+    let Some(date) = ReleaseDate::from_file_name(file_name) else {
+        return (None, file_name);
+    };
+    let separator = |c: char| c.is_whitespace() || c == '_' || c == '[' || c == ']';
+    let rest = file_name.trim_start_matches(separator);
+    let prefix_len = rest.find(separator).unwrap_or(rest.len());
+    (Some(date), rest[prefix_len..].trim_start_matches(separator))
+}
+
+/// Split off a bracketed date at the end, for example " [2019-11-18]" or " [2019]"
+fn split_date_suffix(file_name: &str) -> (&str, Option<ReleaseDate>) {
+    let trimmed = file_name.trim_end();
+    let date = trimmed
+        .strip_suffix(']')
+        .and_then(|text| text.rfind('[').map(|start| (start, &text[start + 1..])))
+        .filter(|(_, date)| date.chars().all(|c| c.is_ascii_digit() || c == '-'))
+        .and_then(|(start, date)| ReleaseDate::parse(date).map(|date| (start, date)));
+    match date {
+        Some((start, date)) => (trimmed[..start].trim_end(), Some(date)),
+        None => (file_name, None),
+    }
+}
+
+fn split_version(text: &str) -> (String, Option<String>) {
+    let text = text.trim();
+    for (open, close) in [('(', ')'), ('[', ']')] {
+        if text.ends_with(close) {
+            if let Some(start) = text.rfind(open) {
+                let version = text[start + 1..text.len() - 1].trim();
+                let title = text[..start].trim();
+                if !title.is_empty() && !version.is_empty() {
+                    return (title.to_owned(), Some(version.to_owned()));
+                }
+            }
+        }
+    }
+    (text.to_owned(), None)
+}
+
+/// Lowercase and collapse whitespace so that "IU" and " iu" are looked up the same
+fn alias_key(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Only keep letters and digits, used to find names that look alike
+fn loose_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+/// Maps artist name variants to a canonical artist
+/// # Fields
+/// * `lookup`: alias key of every canonical name and variant to the canonical name
+/// * `variants`: canonical name to the configured variants
+///
+/// Both are sorted so that lookups don't depend on the order of the config map
+#[derive(Debug, Clone, Default)]
+pub struct AliasTable {
+    lookup: BTreeMap<String, String>,
+    variants: BTreeMap<String, Vec<String>>,
+}
+
+impl AliasTable {
+    /// Build the table from the config, where the key is the canonical artist and the value the
+    /// list of variants. A canonical name always maps to itself, and a variant listed under
+    /// several artists belongs to the first of them in sorted order
+    pub fn new(aliases: &HashMap<String, Vec<String>>) -> Self {
+        let variants = aliases
+            .iter()
+            .map(|(canonical, variants)| (canonical.to_owned(), variants.to_owned()))
+            .collect::<BTreeMap<String, Vec<String>>>();
+        let mut lookup = variants
+            .keys()
+            .map(|canonical| (alias_key(canonical), canonical.to_owned()))
+            .collect::<BTreeMap<String, String>>();
+        for (canonical, names) in variants.iter() {
+            for variant in names {
+                lookup
+                    .entry(alias_key(variant))
+                    .or_insert_with(|| canonical.to_owned());
+            }
+        }
+        Self { lookup, variants }
+    }
+
+    /// Returns the canonical artist if the name or one of its bracketed parts is known
+    pub fn resolve(&self, artist: &str) -> Option<String> {
+        if let Some(canonical) = self.lookup.get(&alias_key(artist)) {
+            return Some(canonical.to_owned());
+        }
+        artist
+            .split(['(', ')', '[', ']'])
+            .map(|part| part.trim())
+            .filter(|part| !part.is_empty() && *part != artist)
+            .find_map(|part| self.lookup.get(&alias_key(part)).cloned())
+    }
+
+    /// Returns the canonical artist, or the name itself if it is not in the table
+    pub fn canonical(&self, artist: &str) -> String {
+        self.resolve(artist).unwrap_or_else(|| artist.to_owned())
+    }
+
+    /// The canonical name and all of its variants
+    pub fn names_of(&self, canonical: &str) -> Vec<String> {
+        let mut names = vec![canonical.to_owned()];
+        if let Some(variants) = self.variants.get(canonical) {
+            names.extend(variants.iter().cloned());
+        }
+        names
+    }
+
+    /// Find artists that are not in the table but look like a variant of a known artist
+    ///
+    /// Returns pairs of the unknown artist and the canonical artist it looks like
+    pub fn unknown_variants<'a, I: IntoIterator<Item = &'a str>>(
+        &self,
+        artists: I,
+    ) -> Vec<(String, String)> {
+        let known = self
+            .lookup
+            .iter()
+            .map(|(key, canonical)| (loose_key(key), canonical))
+            .filter(|(key, _)| !key.is_empty())
+            .collect::<Vec<(String, &String)>>();
+        let unknown = artists
+            .into_iter()
+            .filter(|artist| !artist.is_empty() && self.resolve(artist).is_none())
+            .collect::<BTreeSet<&str>>();
+        unknown
+            .into_iter()
+            .filter_map(|artist| {
+                let key = loose_key(artist);
+                if key.is_empty() {
+                    return None;
+                }
+                // A name that only differs in punctuation wins over one that contains the other
+                known
+                    .iter()
+                    .find(|(known_key, _)| *known_key == key)
+                    .or_else(|| {
+                        known.iter().find(|(known_key, _)| {
+                            (known_key.chars().count() > 2 && key.contains(known_key.as_str()))
+                                || (key.chars().count() > 2 && known_key.contains(key.as_str()))
+                        })
+                    })
+                    .map(|(_, canonical)| (artist.to_owned(), canonical.to_string()))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aliases() -> AliasTable {
+        let mut map = HashMap::new();
+        map.insert(
+            "IU".to_string(),
+            vec!["아이유".to_string(), "IU (아이유)".to_string()],
+        );
+        map.insert(
+            "Girls' Generation".to_string(),
+            vec!["Girls' Generation-TTS".to_string()],
+        );
+        AliasTable::new(&map)
+    }
+
+    #[test]
+    fn test_parse() {
+        let parsed = ParsedName::parse("아이유 - Blueming (Live).mp4", &aliases());
+        assert_eq!(parsed.artist, "IU");
+        assert_eq!(parsed.raw_artist, "아이유");
+        assert_eq!(parsed.title, "Blueming");
+        assert_eq!(parsed.version, Some("Live".to_string()));
+        let parsed = ParsedName::parse("Unknown Title.mkv", &aliases());
+        assert_eq!(parsed.artist, "");
+        assert_eq!(parsed.title, "Unknown Title");
+        assert_eq!(parsed.version, None);
+    }
+
+    #[test]
+    fn test_parse_dates() {
+        let parsed = ParsedName::parse("IU - Blueming (4K) [2019-11-18].mp4", &aliases());
+        assert_eq!(parsed.title, "Blueming");
+        assert_eq!(parsed.version, Some("4K".to_string()));
+        assert_eq!(parsed.date, ReleaseDate::new(2019, 11, 18));
+        let parsed = ParsedName::parse("191118 아이유 - Blueming.mp4", &aliases());
+        assert_eq!(parsed.artist, "IU");
+        assert_eq!(parsed.raw_artist, "아이유");
+        assert_eq!(parsed.date, ReleaseDate::new(2019, 11, 18));
+        let parsed = ParsedName::parse("[2019-11-18] IU - Blueming [MV].mp4", &aliases());
+        assert_eq!(parsed.artist, "IU");
+        assert_eq!(parsed.version, Some("MV".to_string()));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let aliases = aliases();
+        assert_eq!(aliases.canonical("iu"), "IU");
+        assert_eq!(
+            aliases.canonical("girls' generation-tts"),
+            "Girls' Generation"
+        );
+        assert_eq!(aliases.canonical("아이유 (IU)"), "IU");
+        assert_eq!(aliases.canonical("TWICE"), "TWICE");
+    }
+
+    #[test]
+    fn test_conflicting_aliases() {
+        let mut map = HashMap::new();
+        map.insert(
+            "IU".to_string(),
+            vec!["아이유".to_string(), "Lee Ji-eun".to_string()],
+        );
+        map.insert("Lee Ji-eun".to_string(), vec!["아이유".to_string()]);
+        map.insert(
+            "Sunmi".to_string(),
+            vec!["Sunmi (Wonder Girls)".to_string()],
+        );
+        map.insert("Wonder Girls".to_string(), Vec::new());
+        let aliases = AliasTable::new(&map);
+        assert_eq!(aliases.canonical("아이유"), "IU");
+        assert_eq!(aliases.canonical("Lee Ji-eun"), "Lee Ji-eun");
+        assert_eq!(
+            aliases.unknown_variants(vec!["Wonder-Girls", "SUNMI!"]),
+            vec![
+                ("SUNMI!".to_string(), "Sunmi".to_string()),
+                ("Wonder-Girls".to_string(), "Wonder Girls".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_variants() {
+        let aliases = aliases();
+        let report = aliases.unknown_variants(vec!["IU", "I.U.", "Girls Generation", "TWICE"]);
+        assert_eq!(
+            report,
+            vec![
+                (
+                    "Girls Generation".to_string(),
+                    "Girls' Generation".to_string()
+                ),
+                ("I.U.".to_string(), "IU".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+
+/// Videos lined up to play. Played entries stay in the queue so they can be played again with
+/// `previous`
+/// # Fields
+/// * `entries`: Video names in play order
+/// * `position`: Index of the entry that was played last
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayQueue {
+    pub entries: Vec<String>,
+    pub position: Option<usize>,
+}
+
+/// Path of the queue file that belongs to the given data file
+pub fn queue_file_for(data_file: &str) -> String {
+    format!("{}.queue", data_file)
+}
+
+impl PlayQueue {
+    /// Load the queue, an empty queue when the file does not exist or can't be read
+    pub fn load(queue_file: &str) -> Self {
+        fs::read_to_string(queue_file)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save the queue through a temporary file so it is never left half written
+    pub fn save(&self, queue_file: &str) -> std::io::Result<()> {
+        let data = serde_json::to_string_pretty(self)?;
+        let temp_file = format!("{}.tmp", queue_file);
+        let mut file = fs::File::create(&temp_file)?;
+        file.write_all(data.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp_file, queue_file)
+    }
+
+    pub fn add(&mut self, video_name: &str) {
+        self.entries.push(video_name.to_owned());
+    }
+
+    /// Whether there is an entry after the last played one
+    pub fn has_next(&self) -> bool {
+        self.position.map_or(0, |p| p + 1) < self.entries.len()
+    }
+
+    /// Move to the entry after the last played one and return it
+    pub fn next_entry(&mut self) -> Option<String> {
+        let next = self.position.map_or(0, |p| p + 1);
+        let entry = self.entries.get(next)?.to_owned();
+        self.position = Some(next);
+        Some(entry)
+    }
+
+    /// Move to the entry before the last played one and return it
+    pub fn previous_entry(&mut self) -> Option<String> {
+        let previous = self.position?.checked_sub(1)?;
+        let entry = self.entries.get(previous)?.to_owned();
+        self.position = Some(previous);
+        Some(entry)
+    }
+
+    /// Move to the entry at `index` and return it
+    pub fn jump(&mut self, index: usize) -> Option<String> {
+        let entry = self.entries.get(index)?.to_owned();
+        self.position = Some(index);
+        Some(entry)
+    }
+
+    /// Move the entry at `from` to `to`. The last played entry stays the last played one
+    pub fn move_entry(&mut self, from: usize, to: usize) {
+        if from >= self.entries.len() || to >= self.entries.len() {
+            return;
+        }
+        let entry = self.entries.remove(from);
+        self.entries.insert(to, entry);
+        self.position = self.position.map(|p| match p {
+            p if p == from => to,
+            p if from < p && p <= to => p - 1,
+            p if to <= p && p < from => p + 1,
+            p => p,
+        });
+    }
+
+    /// Remove the entries at the indices
+    pub fn remove(&mut self, indices: &[usize]) {
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        for index in indices.into_iter().rev() {
+            if index >= self.entries.len() {
+                continue;
+            }
+            self.entries.remove(index);
+            self.position = match self.position {
+                Some(p) if p > index => Some(p - 1),
+                // The entry before the removed last played one counts as played
+                Some(p) if p == index => p.checked_sub(1),
+                p => p,
+            };
+        }
+    }
+
+    /// Keep only the entries `keep` returns true for
+    pub fn retain(&mut self, keep: impl Fn(&str) -> bool) {
+        let gone = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| !keep(entry))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        self.remove(&gone);
+    }
+
+    /// Point the entries of a renamed video at its new name
+    pub fn rename(&mut self, from: &str, to: &str) {
+        for entry in self.entries.iter_mut().filter(|entry| *entry == from) {
+            *entry = to.to_owned();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.position = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn queue(entries: &[&str]) -> PlayQueue {
+        PlayQueue {
+            entries: entries.iter().map(|e| e.to_string()).collect(),
+            position: None,
+        }
+    }
+
+    #[test]
+    fn test_next_previous() {
+        let mut q = queue(&["a", "b"]);
+        assert_eq!(q.previous_entry(), None);
+        assert_eq!(q.next_entry().as_deref(), Some("a"));
+        assert_eq!(q.next_entry().as_deref(), Some("b"));
+        assert!(!q.has_next());
+        assert_eq!(q.next_entry(), None);
+        assert_eq!(q.previous_entry().as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn test_move_and_remove() {
+        let mut q = queue(&["a", "b", "c", "d"]);
+        q.jump(1);
+        q.move_entry(3, 0);
+        assert_eq!(q.entries, vec!["d", "a", "b", "c"]);
+        assert_eq!(q.position, Some(2));
+        q.move_entry(2, 3);
+        assert_eq!(q.entries, vec!["d", "a", "c", "b"]);
+        assert_eq!(q.position, Some(3));
+        q.remove(&[3, 0]);
+        assert_eq!(q.entries, vec!["a", "c"]);
+        assert_eq!(q.position, Some(1));
+        assert_eq!(q.next_entry(), None);
+        q.clear();
+        assert_eq!(q, PlayQueue::default());
+    }
+
+    #[test]
+    fn test_retain_and_rename() {
+        let mut q = queue(&["a", "b", "c", "b"]);
+        q.jump(2);
+        q.retain(|entry| entry != "b");
+        assert_eq!(q.entries, vec!["a", "c"]);
+        assert_eq!(q.position, Some(1));
+        q.rename("a", "d");
+        assert_eq!(q.entries, vec!["d", "c"]);
+    }
+
+    #[test]
+    fn test_save_load() {
+        let temp_dir = TempDir::new("test_queue").unwrap();
+        let queue_file = temp_dir.path().join("data.json.queue");
+        let queue_file = queue_file.to_str().unwrap();
+        assert_eq!(PlayQueue::load(queue_file), PlayQueue::default());
+        let mut q = queue(&["IU - Blueming.mp4"]);
+        q.next_entry();
+        q.save(queue_file).unwrap();
+        assert_eq!(PlayQueue::load(queue_file), q);
+    }
+}
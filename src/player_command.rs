@@ -0,0 +1,266 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Placeholders that can be used in player commands
+pub const PLACEHOLDERS: [&str; 8] = [
+    "video", "audio", "offset", "start", "end", "volume", "title", "artist",
+];
+
+/// A player command from the config. Either the old comma separated form, where the file is
+/// appended at the end, or a template such as `mpv --audio-file={audio} {video} --no-osc` where
+/// the placeholders are filled in. Templates are split into arguments before the placeholders
+/// are filled in and run without a shell, so the values can't add arguments
+/// # Fields
+/// * `program`: The player binary
+/// * `args`: Arguments that follow the program
+/// * `is_template`: Whether the command places the files itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayerCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    pub is_template: bool,
+}
+
+impl PlayerCommand {
+    pub fn parse(cmd: &str) -> Self {
+        let is_template = PLACEHOLDERS
+            .iter()
+            .any(|name| cmd.contains(&format!("{{{}}}", name)));
+        let mut words = if is_template {
+            split_args(cmd)
+        } else {
+            cmd.split(',').map(|word| word.to_owned()).collect()
+        };
+        let program = if words.is_empty() {
+            String::new()
+        } else {
+            words.remove(0)
+        };
+        Self {
+            program,
+            args: words,
+            is_template,
+        }
+    }
+
+    /// Arguments to run the program with. `extra_args` come first, they are options of the
+    /// backend such as the IPC socket. `files` are appended when the command is not a template
+    pub fn args(
+        &self,
+        extra_args: Vec<String>,
+        files: Vec<String>,
+        values: &HashMap<&str, String>,
+    ) -> Vec<String> {
+        if !self.is_template {
+            return self
+                .args
+                .iter()
+                .cloned()
+                .chain(extra_args)
+                .chain(files)
+                .collect();
+        }
+        extra_args
+            .into_iter()
+            .chain(self.args.iter().filter_map(|arg| fill(arg, values)))
+            .collect()
+    }
+}
+
+/// Player commands for some of the videos, for example a deinterlacing player for `.ts` rips
+/// # Fields
+/// * `extensions`: Video extensions the profile is used for, without the dot
+/// * `categories`: "live", "mv" or tags of the entries the profile is used for
+/// * `video_cmd`: Replaces the configured video command
+/// * `audio_cmd`: Replaces the configured audio command
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerProfile {
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    #[serde(default)]
+    pub video_cmd: Option<String>,
+    #[serde(default)]
+    pub audio_cmd: Option<String>,
+}
+
+impl PlayerProfile {
+    /// Whether the profile is used for a video with the extension and categories. Every
+    /// condition that is set must match
+    pub fn matches(&self, extension: &str, categories: &[String]) -> bool {
+        let extension_matches = self.extensions.is_empty()
+            || self
+                .extensions
+                .iter()
+                .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(extension));
+        let category_matches = self.categories.is_empty()
+            || self.categories.iter().any(|c| {
+                categories
+                    .iter()
+                    .any(|category| c.eq_ignore_ascii_case(category))
+            });
+        (!self.extensions.is_empty() || !self.categories.is_empty())
+            && extension_matches
+            && category_matches
+    }
+}
+
+/// Fill in the placeholders of a template argument. The argument is read once from left to
+/// right, so a value that contains a placeholder is kept as it is. An argument whose placeholders
+/// are all empty is dropped, so `--start={start}` is left out when there is no start
+fn fill(arg: &str, values: &HashMap<&str, String>) -> Option<String> {
+    let mut filled = String::new();
+    let mut used = 0;
+    let mut empty = 0;
+    let mut rest = arg;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let name = rest[1..]
+            .split_once('}')
+            .map(|(name, _)| name)
+            .filter(|name| PLACEHOLDERS.contains(name));
+        let Some(name) = name else {
+            filled.push('{');
+            rest = &rest[1..];
+            continue;
+        };
+        let value = values.get(name).map(|v| v.as_str()).unwrap_or_default();
+        used += 1;
+        if value.is_empty() {
+            empty += 1;
+        }
+        filled.push_str(value);
+        rest = &rest[name.len() + 2..];
+    }
+    filled.push_str(rest);
+    if used > 0 && used == empty {
+        None
+    } else {
+        Some(filled)
+    }
+}
+
+/// Split on whitespace. Single and double quotes group words and a backslash escapes the next
+/// character outside of single quotes
+fn split_args(cmd: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = cmd.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"') | None, '\\') => {
+                current.extend(chars.next());
+                in_word = true;
+            }
+            (Some(_), c) => current.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    args.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        args.push(current);
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_command() {
+        let cmd = PlayerCommand::parse("mpv,--fs");
+        assert!(!cmd.is_template);
+        assert_eq!(
+            cmd.args(
+                vec!["--pause".to_owned()],
+                vec!["a, b.mp4".to_owned()],
+                &HashMap::new()
+            ),
+            vec!["--fs", "--pause", "a, b.mp4"]
+        );
+    }
+
+    #[test]
+    fn test_template_command() {
+        let cmd = PlayerCommand::parse(
+            r#"mpv --audio-file={audio} "--title={artist} - {title}" --start={start} {video} --vf=yadif"#,
+        );
+        assert!(cmd.is_template);
+        assert_eq!(cmd.program, "mpv");
+        let values = HashMap::from([
+            ("video", "IU - Blueming; rm -rf ~.ts".to_owned()),
+            ("audio", "audio/1.flac".to_owned()),
+            ("artist", "IU".to_owned()),
+            ("title", "Blueming".to_owned()),
+            ("start", String::new()),
+        ]);
+        assert_eq!(
+            cmd.args(
+                vec!["--pause".to_owned()],
+                vec!["ignored".to_owned()],
+                &values
+            ),
+            vec![
+                "--pause",
+                "--audio-file=audio/1.flac",
+                "--title=IU - Blueming",
+                "IU - Blueming; rm -rf ~.ts",
+                "--vf=yadif",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_values_with_placeholders() {
+        let cmd = PlayerCommand::parse("mpv --title={title} --start={start} {video} --x={y}");
+        let values = HashMap::from([
+            ("video", "{audio} {start}.mp4".to_owned()),
+            ("title", "{start}".to_owned()),
+            ("audio", "audio/1.flac".to_owned()),
+            ("start", String::new()),
+        ]);
+        assert_eq!(
+            cmd.args(Vec::new(), Vec::new(), &values),
+            vec!["--title={start}", "{audio} {start}.mp4", "--x={y}"]
+        );
+    }
+
+    #[test]
+    fn test_profile_matches() {
+        let profile = PlayerProfile {
+            extensions: vec!["ts".to_owned()],
+            categories: vec!["live".to_owned()],
+            ..Default::default()
+        };
+        assert!(profile.matches("TS", &["live".to_owned()]));
+        assert!(!profile.matches("ts", &["mv".to_owned()]));
+        assert!(!profile.matches("mp4", &["live".to_owned()]));
+        assert!(!PlayerProfile::default().matches("ts", &[]));
+    }
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(
+            split_args(r#"vlc  'a b' c\ d "e \"f\"" '' "#),
+            vec!["vlc", "a b", "c d", "e \"f\"", ""]
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// How long players get to exit after they were asked to before they are killed
+pub const GRACE_PERIOD: Duration = Duration::from_millis(500);
+
+/// A player started by a session
+/// # Fields
+/// * `session`: PID of the mvplayer that started it
+/// * `pid`: PID of the player, which is also its process group
+/// * `program`: The player binary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayerProcess {
+    pub session: u32,
+    pub pid: u32,
+    pub program: String,
+}
+
+impl PlayerProcess {
+    fn line(&self) -> String {
+        format!("{}\t{}\t{}", self.session, self.pid, self.program)
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(3, '\t');
+        Some(Self {
+            session: fields.next()?.parse().ok()?,
+            pid: fields.next()?.parse().ok()?,
+            program: fields.next()?.to_owned(),
+        })
+    }
+}
+
+/// Players of this session and the file they are recorded in
+static PLAYERS: Mutex<Vec<PlayerProcess>> = Mutex::new(Vec::new());
+static PID_FILE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Path of the PID file that belongs to the given data file
+pub fn pid_file_for(data_file: &str) -> String {
+    format!("{}.players", data_file)
+}
+
+/// Record players in `pid_file` from now on. Players left running by a session that is gone
+/// are killed first
+pub fn init(pid_file: &str) {
+    let pid_file = PathBuf::from(pid_file);
+    let (stale, alive) = read(&pid_file)
+        .into_iter()
+        .filter(is_running)
+        .partition::<Vec<_>, _>(|player| !process_exists(player.session));
+    for player in stale {
+        terminate_group(player.pid);
+    }
+    write(&pid_file, &alive);
+    *PID_FILE.lock().unwrap() = Some(pid_file);
+}
+
+/// Record a player started by this session
+pub fn register(pid: u32, program: &str) {
+    let mut players = PLAYERS.lock().unwrap();
+    players.push(PlayerProcess {
+        session: std::process::id(),
+        pid,
+        program: program.to_owned(),
+    });
+    save(&players);
+}
+
+/// Forget a player that exited
+pub fn unregister(pid: u32) {
+    let mut players = PLAYERS.lock().unwrap();
+    players.retain(|player| player.pid != pid);
+    save(&players);
+}
+
+/// Kill every player of this session. Used when the app exits
+pub fn kill_all() {
+    let mut players = PLAYERS.lock().unwrap();
+    for player in players.drain(..) {
+        terminate_group(player.pid);
+    }
+    save(&players);
+}
+
+/// Ask the process group of `pid` to exit and kill it after the grace period. Returns false
+/// when groups can't be signalled on this platform
+pub fn terminate_group(pid: u32) -> bool {
+    if !signal_group(pid, Signal::Terminate) {
+        return false;
+    }
+    let mut waited = Duration::ZERO;
+    while waited < GRACE_PERIOD {
+        if !signal_group(pid, Signal::Check) {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(50));
+        waited += Duration::from_millis(50);
+    }
+    signal_group(pid, Signal::Kill);
+    true
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// Only check whether the group still exists
+    Check,
+    Terminate,
+    Kill,
+}
+
+/// Send the signal to the process group of `pid`. Returns whether the group exists
+#[cfg(unix)]
+pub fn signal_group(pid: u32, signal: Signal) -> bool {
+    let signal = match signal {
+        Signal::Check => 0,
+        Signal::Terminate => libc::SIGTERM,
+        Signal::Kill => libc::SIGKILL,
+    };
+    // A negative PID addresses the whole process group
+    unsafe { libc::kill(-(pid as libc::pid_t), signal) == 0 }
+}
+
+#[cfg(not(unix))]
+pub fn signal_group(_pid: u32, _signal: Signal) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn process_exists(pid: u32) -> bool {
+    unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+
+#[cfg(not(unix))]
+fn process_exists(_pid: u32) -> bool {
+    true
+}
+
+/// Whether the player still runs. Where /proc is available the program name is compared too, so
+/// a reused PID is not mistaken for the player
+fn is_running(player: &PlayerProcess) -> bool {
+    let comm = Path::new("/proc").join(player.pid.to_string()).join("comm");
+    match fs::read_to_string(comm) {
+        Ok(comm) => {
+            let program = Path::new(&player.program)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            // comm is truncated to 15 bytes
+            !comm.trim().is_empty() && program.starts_with(comm.trim())
+        }
+        Err(_) if Path::new("/proc/self").exists() => false,
+        Err(_) => process_exists(player.pid),
+    }
+}
+
+fn read(pid_file: &Path) -> Vec<PlayerProcess> {
+    fs::read_to_string(pid_file)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(PlayerProcess::parse)
+        .collect()
+}
+
+fn write(pid_file: &Path, players: &[PlayerProcess]) {
+    let data = players
+        .iter()
+        .map(|player| player.line() + "\n")
+        .collect::<String>();
+    fs::write(pid_file, data)
+        .unwrap_or_else(|e| eprintln!("Couldn't write {}: {}", pid_file.display(), e));
+}
+
+/// Write the players of this session next to the ones of other running sessions
+fn save(players: &[PlayerProcess]) {
+    let Some(pid_file) = PID_FILE.lock().unwrap().clone() else {
+        return;
+    };
+    let session = std::process::id();
+    let mut all = read(&pid_file)
+        .into_iter()
+        .filter(|player| player.session != session)
+        .collect::<Vec<_>>();
+    all.extend(players.iter().cloned());
+    write(&pid_file, &all);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        let player = PlayerProcess {
+            session: 10,
+            pid: 42,
+            program: "/usr/bin/mpv".to_owned(),
+        };
+        assert_eq!(PlayerProcess::parse(&player.line()), Some(player));
+        assert_eq!(PlayerProcess::parse("10\tmpv"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_is_running() {
+        let exe = std::env::current_exe().unwrap();
+        let mut player = PlayerProcess {
+            session: 1,
+            pid: std::process::id(),
+            program: exe.to_string_lossy().to_string(),
+        };
+        assert!(is_running(&player));
+        player.program = "mpv".to_owned();
+        assert!(!is_running(&player));
+    }
+}
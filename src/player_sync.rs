@@ -0,0 +1,146 @@
+use super::mpv_ipc::MpvIpc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long the players get to load their files before they are started anyway
+const READY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the positions are compared
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A seek that brings the players back in sync
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Correction {
+    SeekAudio(f64),
+    SeekVideo(f64),
+}
+
+/// The seek that fixes drift above `threshold` seconds, or None. The audio should be `offset`
+/// seconds behind the video. The lagging player is moved forward to where it should be
+pub fn correction(
+    video_pos: f64,
+    audio_pos: f64,
+    offset: f64,
+    threshold: f64,
+) -> Option<Correction> {
+    let target_audio = video_pos - offset;
+    let drift = audio_pos - target_audio;
+    if drift.abs() <= threshold {
+        None
+    } else if drift < 0.0 {
+        Some(Correction::SeekAudio(target_audio))
+    } else {
+        Some(Correction::SeekVideo(audio_pos + offset))
+    }
+}
+
+/// Whether the player loaded its file and knows its position
+fn is_loaded(ipc: &mut MpvIpc) -> bool {
+    ipc.is_ready() && ipc.position().is_ok()
+}
+
+/// Keeps two paused mpv players in sync until `stop` is set or a player exits
+/// # Fields
+/// * `audio`, `video`: The players, started with `--pause`
+/// * `offset`: How much later the audio should play than the video, in seconds
+/// * `threshold`: Drift in seconds that is corrected
+/// * `stop`: Set when the players are stopped
+pub struct PlayerSync {
+    pub audio: MpvIpc,
+    pub video: MpvIpc,
+    pub offset: f64,
+    pub threshold: f64,
+    pub stop: Arc<AtomicBool>,
+}
+
+impl PlayerSync {
+    /// Run on a background thread
+    pub fn spawn(self) -> thread::JoinHandle<()> {
+        thread::spawn(move || self.run())
+    }
+
+    fn run(mut self) {
+        let started = Instant::now();
+        while !(is_loaded(&mut self.audio) && is_loaded(&mut self.video)) {
+            if self.stopped() || started.elapsed() > READY_TIMEOUT {
+                break;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        if self.stopped() {
+            return;
+        }
+        self.start_together();
+        loop {
+            thread::sleep(CHECK_INTERVAL);
+            if self.stopped() {
+                return;
+            }
+            let (Ok(video_pos), Ok(audio_pos)) = (self.video.position(), self.audio.position())
+            else {
+                // A player exited
+                return;
+            };
+            // Drift while paused is the user's doing, not the players'
+            if self.video.is_paused().unwrap_or(true) || self.audio.is_paused().unwrap_or(true) {
+                continue;
+            }
+            let result = match correction(video_pos, audio_pos, self.offset, self.threshold) {
+                Some(Correction::SeekAudio(position)) => self.audio.seek_absolute(position),
+                Some(Correction::SeekVideo(position)) => self.video.seek_absolute(position),
+                None => Ok(()),
+            };
+            if result.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Unpause both players at the same moment. With an offset the audio starts later, or starts
+    /// into the track when it should be ahead
+    fn start_together(&mut self) {
+        if self.offset < 0.0 {
+            self.audio.seek_absolute(-self.offset).ok();
+        }
+        if self.offset > 0.0 {
+            self.video.set_paused(false).ok();
+            thread::sleep(Duration::from_secs_f64(self.offset));
+            if !self.stopped() {
+                self.audio.set_paused(false).ok();
+            }
+        } else {
+            self.video.set_paused(false).ok();
+            self.audio.set_paused(false).ok();
+        }
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_correction() {
+        assert_eq!(correction(10.0, 10.05, 0.0, 0.1), None);
+        assert_eq!(
+            correction(10.0, 9.5, 0.0, 0.1),
+            Some(Correction::SeekAudio(10.0))
+        );
+        assert_eq!(
+            correction(10.0, 10.5, 0.0, 0.1),
+            Some(Correction::SeekVideo(10.5))
+        );
+        // The audio should be half a second behind
+        assert_eq!(correction(10.0, 9.5, 0.5, 0.1), None);
+        assert_eq!(
+            correction(10.0, 9.0, 0.5, 0.1),
+            Some(Correction::SeekAudio(9.5))
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::process::{Command, Stdio};
+
+/// What `ffprobe` reports about a media file
+/// # Fields
+/// * `tags`: Format and stream tags with lowercase keys. Format tags win over stream tags
+/// * `duration`: Duration in seconds
+/// * `bit_rate`: Overall bit rate in bits per second
+/// * `width`, `height`: Size of the first video stream
+/// * `audio_streams`: Number of audio streams
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProbeInfo {
+    pub tags: HashMap<String, String>,
+    pub duration: Option<f64>,
+    pub bit_rate: Option<u64>,
+    pub width: Option<u64>,
+    pub height: Option<u64>,
+    pub audio_streams: usize,
+}
+
+impl ProbeInfo {
+    /// Parse the json printed by `ffprobe -print_format json -show_format -show_streams`
+    pub fn from_json(json: &str) -> Option<Self> {
+        let value = serde_json::from_str::<Value>(json).ok()?;
+        let mut info = ProbeInfo::default();
+        let streams = value["streams"].as_array().cloned().unwrap_or_default();
+        for stream in streams.iter() {
+            match stream["codec_type"].as_str() {
+                Some("audio") => info.audio_streams += 1,
+                Some("video") if info.width.is_none() => {
+                    info.width = stream["width"].as_u64();
+                    info.height = stream["height"].as_u64();
+                }
+                _ => {}
+            }
+            add_tags(&mut info.tags, &stream["tags"]);
+        }
+        let format = &value["format"];
+        add_tags(&mut info.tags, &format["tags"]);
+        info.duration = format["duration"].as_str().and_then(|d| d.parse().ok());
+        info.bit_rate = format["bit_rate"].as_str().and_then(|b| b.parse().ok());
+        Some(info)
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.get(name).map(|t| t.as_str())
+    }
+}
+
+fn add_tags(tags: &mut HashMap<String, String>, value: &Value) {
+    if let Some(map) = value.as_object() {
+        for (key, tag) in map {
+            if let Some(tag) = tag.as_str() {
+                tags.insert(key.to_lowercase(), tag.to_owned());
+            }
+        }
+    }
+}
+
+/// Run `ffprobe` on the file. Returns None if ffprobe is missing or cannot read the file
+pub fn probe(path: &str) -> Option<ProbeInfo> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "quiet",
+            "-print_format",
+            "json",
+            "-show_format",
+            "-show_streams",
+        ])
+        .arg(path)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    ProbeInfo::from_json(std::str::from_utf8(&output.stdout).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_json() {
+        let json = r#"{
+            "streams": [
+                {"codec_type": "video", "width": 1920, "height": 1080},
+                {"codec_type": "audio", "tags": {"language": "kor", "TITLE": "stream"}}
+            ],
+            "format": {
+                "duration": "215.040000",
+                "bit_rate": "320000",
+                "tags": {"TITLE": "Blueming", "date": "2019-11-18"}
+            }
+        }"#;
+        let info = ProbeInfo::from_json(json).unwrap();
+        assert_eq!(info.audio_streams, 1);
+        assert_eq!(info.height, Some(1080));
+        assert_eq!(info.duration, Some(215.04));
+        assert_eq!(info.bit_rate, Some(320000));
+        assert_eq!(info.tag("title"), Some("Blueming"));
+        assert_eq!(info.tag("date"), Some("2019-11-18"));
+        assert_eq!(info.tag("language"), Some("kor"));
+    }
+}
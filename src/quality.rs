@@ -0,0 +1,196 @@
+use super::instrumental::base_title;
+use super::probe;
+use std::path::Path;
+
+/// Which copy of a track is preferred when the same track exists in several formats
+/// # Fields
+/// * `format_preference`: File extensions from most to least preferred. Extensions that are not
+///   listed rank below all listed ones
+/// * `prefer_higher_bitrate`: Whether a higher bit rate wins over the format preference
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityPolicy {
+    pub format_preference: Vec<String>,
+    pub prefer_higher_bitrate: bool,
+}
+
+impl Default for QualityPolicy {
+    fn default() -> Self {
+        Self {
+            format_preference: default_format_preference(),
+            prefer_higher_bitrate: false,
+        }
+    }
+}
+
+pub fn default_format_preference() -> Vec<String> {
+    ["flac", "wav", "m4a", "ogg", "aac", "mp3"]
+        .iter()
+        .map(|ext| ext.to_string())
+        .collect()
+}
+
+const LOSSLESS_EXTS: [&str; 2] = ["flac", "wav"];
+
+fn extension(path: &str) -> String {
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+pub fn is_lossless(path: &str) -> bool {
+    LOSSLESS_EXTS.contains(&extension(path).as_str())
+}
+
+/// A copy of a track and its bit rate, if known
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioCopy {
+    pub path: String,
+    pub bit_rate: Option<u64>,
+}
+
+impl AudioCopy {
+    pub fn read(path: &str) -> Self {
+        Self {
+            path: path.to_owned(),
+            bit_rate: probe::probe(path).and_then(|info| info.bit_rate),
+        }
+    }
+}
+
+impl QualityPolicy {
+    fn format_rank(&self, path: &str) -> usize {
+        let ext = extension(path);
+        self.format_preference
+            .iter()
+            .position(|preferred| preferred.eq_ignore_ascii_case(&ext))
+            .unwrap_or(self.format_preference.len())
+    }
+
+    /// Whether `a` is a better copy than `b`
+    pub fn is_better(&self, a: &AudioCopy, b: &AudioCopy) -> bool {
+        let format = self.format_rank(&b.path).cmp(&self.format_rank(&a.path));
+        let bit_rate = a.bit_rate.unwrap_or(0).cmp(&b.bit_rate.unwrap_or(0));
+        let order = if self.prefer_higher_bitrate {
+            bit_rate.then(format)
+        } else {
+            format.then(bit_rate)
+        };
+        order.is_gt()
+    }
+
+    /// The best of the copies, or None if there are none
+    pub fn best<'a>(&self, copies: &'a [AudioCopy]) -> Option<&'a AudioCopy> {
+        copies.iter().fold(None, |best, copy| match best {
+            Some(best) if !self.is_better(copy, best) => Some(best),
+            _ => Some(copy),
+        })
+    }
+}
+
+/// Words that rippers add to folder names to tell copies apart
+const FORMAT_WORDS: [&str; 6] = ["flac", "wav", "m4a", "ogg", "aac", "mp3"];
+
+/// Key that copies of the same track share: the title from the file name without track number,
+/// together with the folder name. Letters and digits only, so "[FLAC] Love poem" and "Love poem"
+/// are not told apart
+pub fn track_key(path: &str) -> String {
+    let file_path = Path::new(path);
+    let title = file_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .map(base_title)
+        .unwrap_or_default();
+    let mut folder = file_path
+        .parent()
+        .and_then(|p| p.file_name())
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    for word in FORMAT_WORDS {
+        folder = folder.replace(word, "");
+    }
+    let folder = folder
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>();
+    format!("{}/{}", folder, title)
+}
+
+/// Other copies of the track among the audio files
+pub fn other_copies(path: &str, audio_files: &[String]) -> Vec<String> {
+    let key = track_key(path);
+    audio_files
+        .iter()
+        .filter(|other| other.as_str() != path && track_key(other) == key)
+        .cloned()
+        .collect()
+}
+
+/// The best copy of the track if it is better than the given one
+pub fn better_copy(path: &str, audio_files: &[String], policy: &QualityPolicy) -> Option<String> {
+    let others = other_copies(path, audio_files);
+    if others.is_empty() {
+        return None;
+    }
+    let current = AudioCopy::read(path);
+    let copies = others
+        .iter()
+        .map(|other| AudioCopy::read(other))
+        .collect::<Vec<AudioCopy>>();
+    policy
+        .best(&copies)
+        .filter(|best| policy.is_better(best, &current))
+        .map(|best| best.path.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn copy(path: &str, bit_rate: u64) -> AudioCopy {
+        AudioCopy {
+            path: path.to_string(),
+            bit_rate: Some(bit_rate),
+        }
+    }
+
+    #[test]
+    fn test_best() {
+        let copies = vec![
+            copy("a.mp3", 320_000),
+            copy("a.flac", 900_000),
+            copy("a.m4a", 256_000),
+        ];
+        let policy = QualityPolicy::default();
+        assert_eq!(policy.best(&copies).unwrap().path, "a.flac");
+        let copies = vec![copy("a.mp3", 320_000), copy("a.m4a", 128_000)];
+        assert_eq!(policy.best(&copies).unwrap().path, "a.m4a");
+        let policy = QualityPolicy {
+            prefer_higher_bitrate: true,
+            ..Default::default()
+        };
+        assert_eq!(policy.best(&copies).unwrap().path, "a.mp3");
+    }
+
+    #[test]
+    fn test_other_copies() {
+        let audio_files = vec![
+            "/music/FLAC/IU - Love poem/01 Blueming.flac".to_string(),
+            "/music/MP3/IU - Love poem [MP3]/01. Blueming.mp3".to_string(),
+            "/music/MP3/IU - Love poem [MP3]/02. Love poem.mp3".to_string(),
+            "/music/MP3/Other/01. Blueming.mp3".to_string(),
+        ];
+        assert_eq!(
+            other_copies(&audio_files[0], &audio_files),
+            vec![audio_files[1].to_owned()]
+        );
+    }
+
+    #[test]
+    fn test_is_lossless() {
+        assert!(is_lossless("a.FLAC"));
+        assert!(!is_lossless("a.mp3"));
+    }
+}
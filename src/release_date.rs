@@ -0,0 +1,266 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A release date. Tags often only have the year, in which case `month` and `day` are 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ReleaseDate {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Display for ReleaseDate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (self.month, self.day) {
+            (0, _) => write!(f, "{:04}", self.year),
+            (_, 0) => write!(f, "{:04}-{:02}", self.year, self.month),
+            _ => write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day),
+        }
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl ReleaseDate {
+    pub fn new(year: i32, month: u32, day: u32) -> Option<Self> {
+        if !(1900..=2100).contains(&year) || month > 12 || (month == 0 && day != 0) {
+            return None;
+        }
+        if month != 0 && day > days_in_month(year, month) {
+            return None;
+        }
+        Some(Self { year, month, day })
+    }
+
+    /// Today's date in UTC
+    pub fn today() -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Self::from_days(secs as i64 / 86400)
+    }
+
+    /// Convert days since 1970-01-01 to a date
+    pub fn from_days(days: i64) -> Self {
+        // Howard Hinnant's civil_from_days
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400 + i64::from(month <= 2)) as i32;
+        Self { year, month, day }
+    }
+
+    /// Parse a date as found in tags or typed in: "2019", "2019-11", "2019-11-18", "2019.11.18",
+    /// "20191118" or a timestamp that starts with one of these
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let digits = text
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect::<String>();
+        if digits.len() == 8 {
+            return Self::new(
+                digits[..4].parse().ok()?,
+                digits[4..6].parse().ok()?,
+                digits[6..].parse().ok()?,
+            );
+        }
+        let mut parts = text
+            .split(|c: char| !c.is_ascii_digit())
+            .filter(|p| !p.is_empty())
+            .take(3);
+        let year = parts.next().filter(|y| y.len() == 4)?.parse().ok()?;
+        let month = parts.next().map(|m| m.parse().ok()).unwrap_or(Some(0))?;
+        let day = parts.next().map(|d| d.parse().ok()).unwrap_or(Some(0))?;
+        Self::new(year, month, day)
+    }
+
+    /// Parse a `YYMMDD` or `YYYY-MM-DD` prefix of a file name, for example
+    /// "191118 IU - Blueming.mp4" or "2019-11-18 IU - Blueming.mp4"
+    pub fn from_file_name(video_name: &str) -> Option<Self> {
+        let file_name = Path::new(video_name).file_name()?.to_str()?;
+        let prefix = file_name
+            .split(|c: char| c.is_whitespace() || c == '_' || c == '[' || c == ']')
+            .find(|p| !p.is_empty())?;
+        let bytes = prefix.as_bytes();
+        if bytes.len() == 10 && bytes[4] == b'-' && bytes[7] == b'-' {
+            return Self::parse(prefix);
+        }
+        if bytes.len() == 6 && bytes.iter().all(|b| b.is_ascii_digit()) {
+            let yy: i32 = prefix[..2].parse().ok()?;
+            // Two digit years more than a year ahead are from the previous century
+            let century = if 2000 + yy > Self::today().year + 1 {
+                1900
+            } else {
+                2000
+            };
+            let date = Self::new(
+                century + yy,
+                prefix[2..4].parse().ok()?,
+                prefix[4..].parse().ok()?,
+            )?;
+            return (date.day != 0).then_some(date);
+        }
+        None
+    }
+
+    /// Days since 1970-01-01, None if the month or day is unknown
+    pub fn to_days(&self) -> Option<i64> {
+        if self.month == 0 || self.day == 0 {
+            return None;
+        }
+        // Howard Hinnant's days_from_civil
+        let year = self.year as i64 - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let mp = (self.month as i64 + 9) % 12;
+        let doy = (153 * mp + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        Some(era * 146097 + doe - 719468)
+    }
+
+    /// Month and day of every day in the Monday to Sunday week of this date
+    pub fn week_days(&self) -> Vec<(u32, u32)> {
+        let Some(days) = self.to_days() else {
+            return Vec::new();
+        };
+        // 1970-01-01 was a Thursday
+        let monday = days - (days + 3).rem_euclid(7);
+        (monday..monday + 7)
+            .map(Self::from_days)
+            .map(|d| (d.month, d.day))
+            .collect()
+    }
+}
+
+/// Videos released on the same month and day as `today` in earlier years, ordered by year. When
+/// there are none, videos released in the same week of an earlier year are returned instead
+///
+/// Returns the videos and whether the search was widened to the week
+pub fn anniversaries(dated: &[(ReleaseDate, String)], today: ReleaseDate) -> (Vec<String>, bool) {
+    let pick = |days: &[(u32, u32)]| {
+        let mut found = dated
+            .iter()
+            .filter(|(date, _)| date.year < today.year && days.contains(&(date.month, date.day)))
+            .collect::<Vec<&(ReleaseDate, String)>>();
+        found.sort();
+        found
+            .into_iter()
+            .map(|(_, name)| name.to_owned())
+            .collect::<Vec<String>>()
+    };
+    let on_the_day = pick(&[(today.month, today.day)]);
+    if !on_the_day.is_empty() {
+        return (on_the_day, false);
+    }
+    (pick(&today.week_days()), true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(ReleaseDate::parse("2019"), ReleaseDate::new(2019, 0, 0));
+        assert_eq!(ReleaseDate::parse("2019-11"), ReleaseDate::new(2019, 11, 0));
+        assert_eq!(
+            ReleaseDate::parse("2019-11-18T00:00:00Z"),
+            ReleaseDate::new(2019, 11, 18)
+        );
+        assert_eq!(
+            ReleaseDate::parse("20191118"),
+            ReleaseDate::new(2019, 11, 18)
+        );
+        assert_eq!(ReleaseDate::parse("2019-13-01"), None);
+        assert_eq!(ReleaseDate::parse("19"), None);
+    }
+
+    #[test]
+    fn test_from_file_name() {
+        assert_eq!(
+            ReleaseDate::from_file_name("191118 IU - Blueming.mp4"),
+            ReleaseDate::new(2019, 11, 18)
+        );
+        assert_eq!(
+            ReleaseDate::from_file_name("2008-08-07_IU - Lost Child.mkv"),
+            ReleaseDate::new(2008, 8, 7)
+        );
+        assert_eq!(
+            ReleaseDate::from_file_name("981103 g.o.d - Observation.ts"),
+            ReleaseDate::new(1998, 11, 3)
+        );
+        assert_eq!(ReleaseDate::from_file_name("IU - 200607.mp4"), None);
+        assert_eq!(ReleaseDate::from_file_name("123456 - Title.mp4"), None);
+    }
+
+    #[test]
+    fn test_from_days() {
+        assert_eq!(
+            ReleaseDate::from_days(0),
+            ReleaseDate::new(1970, 1, 1).unwrap()
+        );
+        assert_eq!(
+            ReleaseDate::from_days(18218),
+            ReleaseDate::new(2019, 11, 18).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            ReleaseDate::new(2019, 11, 0).unwrap().to_string(),
+            "2019-11"
+        );
+        assert_eq!(
+            ReleaseDate::new(2019, 1, 8).unwrap().to_string(),
+            "2019-01-08"
+        );
+    }
+
+    #[test]
+    fn test_to_days() {
+        let date = ReleaseDate::new(2019, 11, 18).unwrap();
+        assert_eq!(date.to_days(), Some(18218));
+        assert_eq!(ReleaseDate::from_days(date.to_days().unwrap()), date);
+        assert_eq!(ReleaseDate::new(2019, 11, 0).unwrap().to_days(), None);
+    }
+
+    #[test]
+    fn test_anniversaries() {
+        let dated = vec![
+            (ReleaseDate::new(2019, 11, 18).unwrap(), "b".to_string()),
+            (ReleaseDate::new(2008, 11, 18).unwrap(), "a".to_string()),
+            (ReleaseDate::new(2015, 11, 20).unwrap(), "c".to_string()),
+            (ReleaseDate::new(2024, 11, 18).unwrap(), "d".to_string()),
+        ];
+        let today = ReleaseDate::new(2024, 11, 18).unwrap();
+        assert_eq!(
+            anniversaries(&dated, today),
+            (vec!["a".to_string(), "b".to_string()], false)
+        );
+        // 2024-11-19 is a Tuesday, its week runs from the 18th to the 24th
+        let today = ReleaseDate::new(2024, 11, 19).unwrap();
+        assert_eq!(
+            anniversaries(&dated, today),
+            (
+                vec!["a".to_string(), "c".to_string(), "b".to_string()],
+                true
+            )
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use super::release_date::ReleaseDate;
+use super::unicode_paths;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+pub const DEFAULT_TEMPLATE: &str = "{artist} - {title} ({version}) [{date}]";
+
+/// Values for the placeholders of a rename template
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NameFields {
+    pub artist: String,
+    pub title: String,
+    pub version: Option<String>,
+    pub date: Option<ReleaseDate>,
+}
+
+/// A video that is renamed, both are full paths
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rename {
+    pub from: String,
+    pub to: String,
+}
+
+/// Why renaming videos failed
+/// # Variants
+/// * `NotRenamed`: Nothing was changed, the files that were already renamed were moved back
+/// * `NotSaved`: The files and the data file have the new names, but the queue or the meta with
+///   the new names could not be saved
+#[derive(Debug)]
+pub enum RenameError {
+    NotRenamed(std::io::Error),
+    NotSaved {
+        renamed: usize,
+        error: std::io::Error,
+    },
+}
+
+impl Display for RenameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenameError::NotRenamed(error) => {
+                write!(f, "Couldn't rename, nothing was changed: {}", error)
+            }
+            RenameError::NotSaved { renamed, error } => write!(
+                f,
+                "Renamed {} videos, but the queue or meta still has the old names: {}",
+                renamed, error
+            ),
+        }
+    }
+}
+
+/// Build a file name from the template. `{artist}`, `{title}`, `{version}`, `{date}` and `{year}`
+/// are replaced, and a bracketed part whose placeholders are all empty is left out
+pub fn render(template: &str, fields: &NameFields) -> String {
+    let value = |name: &str| match name {
+        "artist" => fields.artist.to_owned(),
+        "title" => fields.title.to_owned(),
+        "version" => fields.version.clone().unwrap_or_default(),
+        "date" => fields.date.map(|d| d.to_string()).unwrap_or_default(),
+        "year" => fields.date.map(|d| d.year.to_string()).unwrap_or_default(),
+        _ => format!("{{{}}}", name),
+    };
+    let mut name = String::new();
+    // Text of the bracketed part being built and whether any of its placeholders had a value
+    let mut group: Option<(String, bool)> = None;
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        let text = match c {
+            '{' => {
+                let placeholder = chars.by_ref().take_while(|c| *c != '}').collect::<String>();
+                let text = value(&placeholder);
+                if let Some((_, filled)) = group.as_mut() {
+                    *filled |= !text.is_empty();
+                }
+                text
+            }
+            '(' | '[' if group.is_none() => {
+                group = Some((c.to_string(), false));
+                continue;
+            }
+            ')' | ']' if group.is_some() => {
+                let (text, filled) = group.take().unwrap();
+                if filled {
+                    name.push_str(&text);
+                    name.push(c);
+                }
+                continue;
+            }
+            c => c.to_string(),
+        };
+        match group.as_mut() {
+            Some((group_text, _)) => group_text.push_str(&text),
+            None => name.push_str(&text),
+        }
+    }
+    if let Some((text, _)) = group {
+        name.push_str(&text);
+    }
+    sanitize(&name)
+}
+
+/// Replace characters that are not allowed in file names and collapse whitespace
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .trim_end_matches('.')
+        .to_owned()
+}
+
+/// The path the video gets with the new name, keeping its directory and extension
+pub fn renamed_path(video_path: &str, new_name: &str) -> String {
+    let path = Path::new(video_path);
+    let file_name = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}.{}", new_name, ext),
+        None => new_name.to_owned(),
+    };
+    path.with_file_name(file_name).to_str().unwrap().to_owned()
+}
+
+/// Split the renames into the ones that can be done and the ones that would overwrite a file or
+/// give two videos the same name, with the reason. Videos that keep their name are left out
+pub fn plan(renames: Vec<Rename>) -> (Vec<Rename>, Vec<(Rename, String)>) {
+    let mut targets = HashSet::new();
+    let mut ok = Vec::new();
+    let mut conflicts = Vec::new();
+    for rename in renames {
+        let source = unicode_paths::nfc(&rename.from);
+        let target = unicode_paths::nfc(&rename.to);
+        if source == target {
+            continue;
+        }
+        // A change of case only finds the video itself on case insensitive file systems
+        let case_change = source.to_lowercase() == target.to_lowercase();
+        if !targets.insert(target.to_lowercase()) {
+            conflicts.push((rename, "Another video gets the same name".to_owned()));
+        } else if !case_change && Path::new(&rename.to).exists() {
+            conflicts.push((rename, "A file with the new name exists".to_owned()));
+        } else {
+            ok.push(rename);
+        }
+    }
+    (ok, conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_render() {
+        let mut fields = NameFields {
+            artist: "IU".to_owned(),
+            title: "Blueming".to_owned(),
+            version: None,
+            date: ReleaseDate::new(2019, 11, 18),
+        };
+        assert_eq!(
+            render(DEFAULT_TEMPLATE, &fields),
+            "IU - Blueming [2019-11-18]"
+        );
+        fields.version = Some("4K".to_owned());
+        fields.date = None;
+        assert_eq!(render(DEFAULT_TEMPLATE, &fields), "IU - Blueming (4K)");
+        fields.title = "Who/What?".to_owned();
+        assert_eq!(
+            render("{year} {artist} - {title}", &fields),
+            "IU - Who_What_"
+        );
+    }
+
+    #[test]
+    fn test_plan() {
+        let temp_dir = TempDir::new("test_plan").unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+        std::fs::write(temp_dir.path().join("taken.mp4"), "").unwrap();
+        let rename = |from: &str, to: &str| Rename {
+            from: format!("{}/{}", dir, from),
+            to: renamed_path(&format!("{}/{}", dir, from), to),
+        };
+        let (ok, conflicts) = plan(vec![
+            rename("a.mp4", "A"),
+            rename("b.mp4", "b"),
+            rename("c.mkv", "taken"),
+            rename("d.mp4", "A"),
+            rename("e.mp4", "taken"),
+        ]);
+        assert_eq!(ok, vec![rename("a.mp4", "A"), rename("c.mkv", "taken")]);
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].0, rename("d.mp4", "A"));
+        assert_eq!(conflicts[1].0, rename("e.mp4", "taken"));
+    }
+}
@@ -0,0 +1,182 @@
+use std::process::{Command, Stdio};
+
+/// Sample rate the audio is decoded at. Beats do not need more than this
+const SAMPLE_RATE: u32 = 11025;
+
+/// Samples per onset frame
+const FRAME: usize = 256;
+
+/// Tempo range that estimates are folded into
+const MIN_BPM: f64 = 70.0;
+const MAX_BPM: f64 = 180.0;
+
+/// Tempo and overall energy of a track
+/// # Fields
+/// * `bpm`: Estimated beats per minute
+/// * `energy`: Root mean square level of the track from 0 to 100
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tempo {
+    pub bpm: f64,
+    pub energy: f64,
+}
+
+/// Decode up to two minutes of the audio from `skip` seconds in as mono samples with ffmpeg
+fn decode(path: &str, skip: u32) -> Option<Vec<f32>> {
+    let output = Command::new("ffmpeg")
+        .args(["-nostats", "-hide_banner", "-loglevel", "error"])
+        .args(["-ss", &skip.to_string(), "-t", "120", "-i"])
+        .arg(path)
+        .args(["-map", "0:a:0", "-ac", "1", "-ar", &SAMPLE_RATE.to_string()])
+        .args(["-f", "s16le", "-"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    Some(
+        output
+            .stdout
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / i16::MAX as f32)
+            .collect(),
+    )
+}
+
+/// Root mean square level of the samples from 0 to 100
+pub fn energy(samples: &[f32]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let sum = samples.iter().map(|s| (*s as f64).powi(2)).sum::<f64>();
+    ((sum / samples.len() as f64).sqrt() * 1000.0).round() / 10.0
+}
+
+/// Estimate the tempo from how often rises in loudness repeat
+pub fn estimate_bpm(samples: &[f32], sample_rate: u32) -> Option<f64> {
+    let frame_energy = samples
+        .chunks_exact(FRAME)
+        .map(|frame| frame.iter().map(|s| s * s).sum::<f32>())
+        .collect::<Vec<f32>>();
+    let onsets = frame_energy
+        .windows(2)
+        .map(|w| (w[1] - w[0]).max(0.0))
+        .collect::<Vec<f32>>();
+    let frames_per_minute = 60.0 * sample_rate as f64 / FRAME as f64;
+    let min_lag = (frames_per_minute / MAX_BPM).floor() as usize;
+    let max_lag = (frames_per_minute / MIN_BPM).ceil() as usize;
+    if onsets.len() < max_lag * 4 {
+        return None;
+    }
+    let correlation = |lag: usize| {
+        onsets
+            .iter()
+            .zip(&onsets[lag..])
+            .map(|(a, b)| (a * b) as f64)
+            .sum::<f64>()
+    };
+    let (best_lag, best) = (min_lag..=max_lag)
+        .map(|lag| (lag, correlation(lag)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    if best <= 0.0 {
+        return None;
+    }
+    // Refine the peak between neighbouring lags
+    let (before, after) = (correlation(best_lag - 1), correlation(best_lag + 1));
+    let denominator = before - 2.0 * best + after;
+    let shift = if denominator != 0.0 {
+        (0.5 * (before - after) / denominator).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    let bpm = frames_per_minute / (best_lag as f64 + shift);
+    Some((bpm * 10.0).round() / 10.0)
+}
+
+/// Decode the audio file and work out its tempo and energy
+pub fn analyze(path: &str) -> Option<Tempo> {
+    // Intros are often quiet or free tempo, short tracks are read from the start
+    let mut samples = decode(path, 30)?;
+    if samples.len() < SAMPLE_RATE as usize * 10 {
+        samples = decode(path, 0)?;
+    }
+    Some(Tempo {
+        bpm: estimate_bpm(&samples, SAMPLE_RATE)?,
+        energy: energy(&samples),
+    })
+}
+
+/// Parse a BPM range like "bpm:120-130", "bpm:120-", "bpm:-100" or "bpm:128". The prefix is
+/// optional and a single value matches tempos that round to it
+pub fn parse_bpm_range(query: &str) -> Option<(u32, u32)> {
+    let query = query.trim();
+    let range = query
+        .strip_prefix("bpm:")
+        .or_else(|| query.strip_prefix("BPM:"))
+        .unwrap_or(query)
+        .trim();
+    let parse = |value: &str, default: u32| match value.trim() {
+        "" => Some(default),
+        value => value.parse::<u32>().ok(),
+    };
+    let (min, max) = match range.split_once('-') {
+        Some((min, max)) => (parse(min, 0)?, parse(max, u32::MAX)?),
+        None if !range.is_empty() => {
+            let bpm = range.parse::<u32>().ok()?;
+            (bpm, bpm)
+        }
+        None => return None,
+    };
+    (min <= max).then_some((min, max))
+}
+
+/// Whether the tempo is within the range from `parse_bpm_range`
+pub fn in_range(bpm: f64, (min, max): (u32, u32)) -> bool {
+    let bpm = bpm.round();
+    bpm >= min as f64 && bpm <= max as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn click_track(bpm: f64, seconds: usize) -> Vec<f32> {
+        let beat = (SAMPLE_RATE as f64 * 60.0 / bpm) as usize;
+        (0..SAMPLE_RATE as usize * seconds)
+            .map(|i| if i % beat < 200 { 0.8 } else { 0.01 })
+            .collect()
+    }
+
+    #[test]
+    fn test_estimate_bpm() {
+        for bpm in [90.0, 120.0, 128.0, 150.0] {
+            let estimate = estimate_bpm(&click_track(bpm, 30), SAMPLE_RATE).unwrap();
+            assert!(
+                (estimate - bpm).abs() < 2.0,
+                "{} estimated as {}",
+                bpm,
+                estimate
+            );
+        }
+        assert_eq!(estimate_bpm(&[0.0; 1000], SAMPLE_RATE), None);
+        assert_eq!(
+            estimate_bpm(&vec![0.0; SAMPLE_RATE as usize * 30], SAMPLE_RATE),
+            None
+        );
+    }
+
+    #[test]
+    fn test_energy() {
+        assert_eq!(energy(&[]), 0.0);
+        assert_eq!(energy(&[0.5, -0.5]), 50.0);
+    }
+
+    #[test]
+    fn test_parse_bpm_range() {
+        assert_eq!(parse_bpm_range("bpm:120-130"), Some((120, 130)));
+        assert_eq!(parse_bpm_range("120-"), Some((120, u32::MAX)));
+        assert_eq!(parse_bpm_range("bpm:-100"), Some((0, 100)));
+        assert_eq!(parse_bpm_range("bpm:128"), Some((128, 128)));
+        assert_eq!(parse_bpm_range("bpm:130-120"), None);
+        assert_eq!(parse_bpm_range("fast"), None);
+        assert!(in_range(127.6, (128, 128)));
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread::{self, JoinHandle};
+
+/// How images are drawn in the fzf preview pane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageProtocol {
+    Kitty,
+    Sixel,
+    Chafa,
+    Disabled,
+}
+
+impl ImageProtocol {
+    /// Protocol named in the config, or the one the terminal supports when there is no name
+    pub fn from_config(name: Option<&str>) -> Self {
+        match name.map(|n| n.trim().to_lowercase()).as_deref() {
+            Some("kitty") => ImageProtocol::Kitty,
+            Some("sixel") => ImageProtocol::Sixel,
+            Some("chafa") => ImageProtocol::Chafa,
+            Some("none") | Some("off") => ImageProtocol::Disabled,
+            _ => Self::detect(),
+        }
+    }
+
+    fn detect() -> Self {
+        let term = std::env::var("TERM").unwrap_or_default();
+        if std::env::var_os("KITTY_WINDOW_ID").is_some() || term.contains("kitty") {
+            ImageProtocol::Kitty
+        } else if term.contains("foot") || term.contains("mlterm") || term.contains("sixel") {
+            ImageProtocol::Sixel
+        } else {
+            ImageProtocol::Chafa
+        }
+    }
+
+    /// fzf `--preview` command that draws the thumbnail of the video on the selected line.
+    /// Lines without a thumbnail, such as menu options, show nothing
+    pub fn preview_command(&self, cache_dir: &str) -> Option<String> {
+        let size = "${FZF_PREVIEW_COLUMNS}x${FZF_PREVIEW_LINES}";
+        let viewer = match self {
+            ImageProtocol::Kitty => format!(
+                "kitty +kitten icat --clear --transfer-mode=memory --stdin=no --place={}@0x0",
+                size
+            ),
+            ImageProtocol::Sixel => format!("chafa --format=sixels --size={}", size),
+            ImageProtocol::Chafa => format!("chafa --format=symbols --size={}", size),
+            ImageProtocol::Disabled => return None,
+        };
+        let dir = thumbnail_dir(cache_dir);
+        Some(format!(
+            "f={}/{{1}}.jpg; [ -f \"$f\" ] && {} \"$f\"",
+            shell_quote(&dir.to_string_lossy()),
+            viewer
+        ))
+    }
+}
+
+/// Quote for sh so the text is passed as is
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+pub fn thumbnail_dir(cache_dir: &str) -> PathBuf {
+    Path::new(cache_dir).join("thumbnails")
+}
+
+/// Where the thumbnail of a video is cached. The video name is kept so the preview command can
+/// find it from the selected line
+pub fn thumbnail_path(cache_dir: &str, video_name: &str) -> PathBuf {
+    thumbnail_dir(cache_dir).join(format!("{}.jpg", video_name))
+}
+
+/// Make a thumbnail from a representative frame near the start of the video
+pub fn generate(video_path: &Path, thumbnail: &Path) -> bool {
+    if let Some(parent) = thumbnail.parent() {
+        if std::fs::create_dir_all(parent).is_err() {
+            return false;
+        }
+    }
+    // Written under another name first so the preview never shows a partial image
+    let part = thumbnail.with_extension("part.jpg");
+    // Videos shorter than the seek point are read from the start
+    let made = ["15", "0"].iter().any(|start| {
+        Command::new("ffmpeg")
+            .args(["-nostats", "-hide_banner", "-loglevel", "error", "-y"])
+            .args(["-ss", start, "-i"])
+            .arg(video_path)
+            .args(["-vf", "thumbnail,scale=480:-2", "-frames:v", "1"])
+            .arg(&part)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+            && part.metadata().is_ok_and(|m| m.len() > 0)
+    });
+    made && std::fs::rename(&part, thumbnail).is_ok()
+}
+
+/// Make the thumbnails that are not cached yet on a background thread
+///
+/// `videos` are pairs of video path and thumbnail path. Returns the number of thumbnails made
+pub fn generate_missing(videos: Vec<(PathBuf, PathBuf)>) -> JoinHandle<usize> {
+    thread::spawn(move || {
+        videos
+            .iter()
+            .filter(|(_, thumbnail)| !thumbnail.exists())
+            .filter(|(video, thumbnail)| generate(video, thumbnail))
+            .count()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_command() {
+        assert_eq!(
+            thumbnail_path("/tmp/cache", "IU - Blueming.mp4"),
+            PathBuf::from("/tmp/cache/thumbnails/IU - Blueming.mp4.jpg")
+        );
+        let command = ImageProtocol::Chafa
+            .preview_command("/tmp/it's cache")
+            .unwrap();
+        assert!(command.starts_with("f='/tmp/it'\\''s cache/thumbnails'/{1}.jpg; "));
+        assert!(command.ends_with(
+            "chafa --format=symbols --size=${FZF_PREVIEW_COLUMNS}x${FZF_PREVIEW_LINES} \"$f\""
+        ));
+        assert_eq!(ImageProtocol::Disabled.preview_command("/tmp"), None);
+        assert_eq!(
+            ImageProtocol::from_config(Some("Sixel")),
+            ImageProtocol::Sixel
+        );
+    }
+}
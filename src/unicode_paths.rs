@@ -0,0 +1,105 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+use unicode_normalization::UnicodeNormalization;
+use walkdir::WalkDir;
+
+/// NFC form of a path, used as the key when matching paths that may be in NFD on disk
+pub fn nfc(path: &str) -> String {
+    path.nfc().collect()
+}
+
+/// Map from the NFC form of every file path under the directory to its form on disk
+pub fn index_dir(dir: &str, recursive: bool) -> HashMap<String, String> {
+    let max_depth = if recursive { usize::MAX } else { 1 };
+    WalkDir::new(dir)
+        .max_depth(max_depth)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.path().to_str().map(|p| p.to_string()))
+        .map(|path| (nfc(&path), path))
+        .collect()
+}
+
+/// Looks up the form a path has on disk. The index of the directory is only built the first time
+/// a path does not exist as given
+pub struct DiskIndex {
+    dir: String,
+    recursive: bool,
+    index: Option<HashMap<String, String>>,
+}
+
+impl DiskIndex {
+    pub fn new(dir: &str, recursive: bool) -> Self {
+        Self {
+            dir: dir.to_owned(),
+            recursive,
+            index: None,
+        }
+    }
+
+    /// Returns the path as it is on disk, or None if no form of it exists
+    pub fn resolve(&mut self, path: &str) -> Option<String> {
+        if Path::new(path).exists() {
+            return Some(path.to_owned());
+        }
+        self.index
+            .get_or_insert_with(|| index_dir(&self.dir, self.recursive))
+            .get(&nfc(path))
+            .cloned()
+    }
+}
+
+/// Find files under the directory whose names exist in more than one normalization form, for
+/// example once in NFC and once in NFD
+///
+/// Returns the NFC path and every form found on disk
+pub fn mixed_forms(dir: &str) -> Vec<(String, Vec<String>)> {
+    let mut forms: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.path().to_str().map(|p| p.to_string()))
+        .for_each(|path| {
+            forms.entry(nfc(&path)).or_default().insert(path);
+        });
+    forms
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(key, paths)| (key, paths.into_iter().collect()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempdir::TempDir;
+
+    const NFC_NAME: &str = "\u{c544}\u{c774}\u{c720}.mp4";
+    const NFD_NAME: &str = "\u{110b}\u{1161}\u{110b}\u{1175}\u{110b}\u{1172}.mp4";
+
+    #[test]
+    fn test_disk_index_resolve() {
+        let temp_dir = TempDir::new("test_disk_index_resolve").unwrap();
+        let nfd_path = temp_dir.path().join(NFD_NAME);
+        fs::File::create(&nfd_path).unwrap();
+        let nfc_path = temp_dir.path().join(NFC_NAME);
+        let mut index = DiskIndex::new(temp_dir.path().to_str().unwrap(), false);
+        assert_eq!(
+            index.resolve(nfc_path.to_str().unwrap()),
+            Some(nfd_path.to_str().unwrap().to_string())
+        );
+    }
+
+    #[test]
+    fn test_mixed_forms() {
+        let temp_dir = TempDir::new("test_mixed_forms").unwrap();
+        fs::File::create(temp_dir.path().join(NFD_NAME)).unwrap();
+        fs::File::create(temp_dir.path().join(NFC_NAME)).unwrap();
+        fs::File::create(temp_dir.path().join("other.mp4")).unwrap();
+        let mixed = mixed_forms(temp_dir.path().to_str().unwrap());
+        assert_eq!(mixed.len(), 1);
+        assert_eq!(mixed[0].1.len(), 2);
+    }
+}
@@ -0,0 +1,151 @@
+use crate::entry_meta::{EntryMeta, MAX_RATING};
+use crate::release_date::ReleaseDate;
+use crate::views::clear_term;
+
+use super::fzf_selector::{FzfSelector, SelectType};
+use super::tag_picker::TagPicker;
+use std::collections::BTreeSet;
+
+/// Edit the tags, rating and favourite flag of a single entry
+pub struct EntryEditor {
+    video_name: String,
+    meta: EntryMeta,
+    all_tags: BTreeSet<String>,
+}
+
+impl EntryEditor {
+    pub fn new(video_name: String, meta: EntryMeta, all_tags: BTreeSet<String>) -> Self {
+        Self {
+            video_name,
+            meta,
+            all_tags,
+        }
+    }
+
+    /// Returns the edited meta once the user goes back
+    pub fn start(mut self) -> EntryMeta {
+        loop {
+            clear_term(&self.header())
+                .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+            let fzf_view = FzfSelector::new(
+                None,
+                Some(vec![
+                    "[[Edit Tags]]".to_owned(),
+                    "[[Set Rating]]".to_owned(),
+                    "[[Toggle Favourite]]".to_owned(),
+                    "[[Set Release Date]]".to_owned(),
+                    "[[Set Sync Offset]]".to_owned(),
+                    "[[Back]]".to_owned(),
+                ]),
+                None,
+            );
+            match fzf_view.fzf_select(SelectType::Single).as_str() {
+                "[[Edit Tags]]" => {
+                    let tag_picker = TagPicker::new(self.all_tags.clone(), self.meta.tags.clone());
+                    for tag in tag_picker.start() {
+                        self.meta.toggle_tag(&tag);
+                        self.all_tags.insert(tag);
+                    }
+                }
+                "[[Set Rating]]" => {
+                    if let Some(rating) = Self::pick_rating() {
+                        self.meta.set_rating(rating);
+                    }
+                }
+                "[[Toggle Favourite]]" => self.meta.favourite = !self.meta.favourite,
+                "[[Set Release Date]]" => {
+                    if let Some(date) = Self::pick_release_date() {
+                        self.meta.release_date = date.map(|d| d.to_string());
+                    }
+                }
+                "[[Set Sync Offset]]" => {
+                    if let Some(offset) = Self::pick_sync_offset() {
+                        self.meta.sync_offset_ms = offset;
+                    }
+                }
+                _ => return self.meta,
+            }
+        }
+    }
+
+    fn header(&self) -> String {
+        let tags = self
+            .meta
+            .tags
+            .iter()
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(", ");
+        format!(
+            "Editing {}\nTags: {}\nRating: {}\nFavourite: {}\nRelease date: {}\nSync offset: {}ms\n",
+            self.video_name,
+            tags,
+            stars(self.meta.rating),
+            if self.meta.favourite { "yes" } else { "no" },
+            self.meta.release_date.as_deref().unwrap_or("not set"),
+            self.meta.sync_offset_ms
+        )
+    }
+
+    /// Returns None when nothing was picked and Some(None) to clear the rating
+    fn pick_rating() -> Option<Option<u8>> {
+        let mut options = (1..=MAX_RATING)
+            .rev()
+            .map(|r| stars(Some(r)))
+            .collect::<Vec<String>>();
+        options.push("No rating".to_owned());
+        let fzf_view = FzfSelector::new(Some(options), None, None);
+        let selected = fzf_view.fzf_select(SelectType::Single);
+        if selected.is_empty() {
+            return None;
+        }
+        Some(parse_stars(&selected))
+    }
+
+    /// Returns None when nothing valid was entered and Some(None) to clear the date
+    fn pick_release_date() -> Option<Option<ReleaseDate>> {
+        clear_term("Type a release date as YYYY-MM-DD, YYYY-MM or YYYY and press enter")
+            .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let fzf_view = FzfSelector::new(None, Some(vec!["[[Clear]]".to_owned()]), None);
+        let (query, selected) = fzf_view.fzf_select_with_query(SelectType::Single);
+        if selected.iter().any(|s| s == "[[Clear]]") {
+            return Some(None);
+        }
+        ReleaseDate::parse(&query).map(Some)
+    }
+
+    /// Returns None when nothing valid was entered
+    fn pick_sync_offset() -> Option<i64> {
+        clear_term(
+            "Type how many milliseconds later the audio should play than the video, \
+             negative to play it earlier, and press enter",
+        )
+        .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let fzf_view = FzfSelector::new(None, Some(vec!["[[Reset]]".to_owned()]), None);
+        let (query, selected) = fzf_view.fzf_select_with_query(SelectType::Single);
+        if selected.iter().any(|s| s == "[[Reset]]") {
+            return Some(0);
+        }
+        query.parse().ok()
+    }
+}
+
+/// Display a rating as stars, for example "★★★☆☆"
+pub fn stars(rating: Option<u8>) -> String {
+    match rating {
+        None => "No rating".to_owned(),
+        Some(r) => (1..=MAX_RATING)
+            .map(|i| if i <= r { '★' } else { '☆' })
+            .collect(),
+    }
+}
+
+/// Count the stars in a line produced by `stars`
+pub fn parse_stars(selected: &str) -> Option<u8> {
+    let count = selected.chars().filter(|c| *c == '★').count() as u8;
+    if count == 0 {
+        None
+    } else {
+        Some(count)
+    }
+}
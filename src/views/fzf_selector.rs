@@ -1,11 +1,16 @@
+use std::collections::HashMap;
 use std::io::Write;
 use std::process::{Command, Stdio};
 use std::str;
 
+const SEARCH_KEY_SEPARATOR: char = '\t';
+
 pub struct FzfSelector {
     inputs: Vec<String>,
     other_options: Vec<String>,
     height: String,
+    search_keys: HashMap<String, String>,
+    preview: Option<String>,
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -21,23 +26,67 @@ impl FzfSelector {
         height: Option<String>,
     ) -> FzfSelector {
         Self {
-            inputs: inputs.unwrap_or(Vec::new()),
-            other_options: other_options.unwrap_or(Vec::new()),
+            inputs: inputs.unwrap_or_default(),
+            other_options: other_options.unwrap_or_default(),
             height: height.unwrap_or("80%".to_string()),
+            search_keys: HashMap::new(),
+            preview: None,
         }
     }
 
+    /// Extra text per input that fzf matches on. The keys are shown dimmed after the input and
+    /// are removed from the selection, so the original input is still returned
+    pub fn with_search_keys(mut self, search_keys: HashMap<String, String>) -> Self {
+        self.search_keys = search_keys;
+        self
+    }
+
+    /// Command that fzf runs to fill a preview pane next to the list. `{1}` is the selected line
+    /// without its search keys
+    pub fn with_preview(mut self, preview: Option<String>) -> Self {
+        self.preview = preview;
+        self
+    }
+
     pub fn fzf_select(self, select_type: SelectType) -> String {
+        let output = self.run_fzf(select_type, &[]);
+        output
+            .trim()
+            .lines()
+            .map(strip_search_keys)
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+
+    /// Same as `fzf_select` but also returns the query that was typed. Used when free text is
+    /// accepted in addition to the listed options
+    ///
+    /// Returns the query and the selected lines
+    pub fn fzf_select_with_query(self, select_type: SelectType) -> (String, Vec<String>) {
+        let output = self.run_fzf(select_type, &["--print-query"]);
+        let mut lines = output.lines();
+        let query = lines.next().unwrap_or_default().trim().to_owned();
+        let selected = lines
+            .map(|line| strip_search_keys(line).trim().to_owned())
+            .filter(|line| !line.is_empty())
+            .collect();
+        (query, selected)
+    }
+
+    fn run_fzf(&self, select_type: SelectType, extra_args: &[&str]) -> String {
         let mut fzf_in = String::new();
         for input in self.inputs.iter() {
             fzf_in.push_str(input);
+            if let Some(keys) = self.search_keys.get(input) {
+                fzf_in.push_str(&format!("{}\x1b[2m{}\x1b[0m", SEARCH_KEY_SEPARATOR, keys));
+            }
             fzf_in.push('\n');
         }
         for option in self.other_options.iter() {
             fzf_in.push_str(option);
             fzf_in.push('\n');
         }
-        let args = match select_type {
+        let mut args = match select_type {
             SelectType::Single => vec![
                 "-i",
                 "--height",
@@ -54,6 +103,15 @@ impl FzfSelector {
                 "-m",
             ],
         };
+        if !self.search_keys.is_empty() {
+            args.push("--ansi");
+        }
+        let delimiter = format!("--delimiter={}", SEARCH_KEY_SEPARATOR);
+        let preview_window = "--preview-window=right,50%";
+        if let Some(preview) = &self.preview {
+            args.extend_from_slice(&[&delimiter, "--preview", preview, preview_window]);
+        }
+        args.extend_from_slice(extra_args);
         let mut child = Command::new("fzf")
             .args(args)
             .stdin(Stdio::piped())
@@ -64,9 +122,14 @@ impl FzfSelector {
         stdin
             .write_all(fzf_in.as_bytes())
             .expect("Failed to write fzf_input to fzf command stdin");
+        drop(stdin);
         let output = child
             .wait_with_output()
             .expect("Failed to read fzf command stdout");
-        String::from(str::from_utf8(&output.stdout).unwrap().trim())
+        String::from(str::from_utf8(&output.stdout).unwrap())
     }
 }
+
+fn strip_search_keys(line: &str) -> &str {
+    line.split(SEARCH_KEY_SEPARATOR).next().unwrap_or_default()
+}
@@ -12,11 +12,38 @@ pub enum MenuOptions {
     SortAsc,
     SortDesc,
     SortMtime,
+    SortReleaseDate,
+    SortBpm,
     Random,
     Quit,
     Update,
     SearchFilter,
     ClearPlayed,
+    EditEntry,
+    ToggleFavourites,
+    TagFilter,
+    RatingFilter,
+    ArtistReport,
+    UnicodeReport,
+    BrowseEras,
+    OnThisDay,
+    PlayNext,
+    ToggleLyrics,
+    ToggleKaraoke,
+    DetectInstrumentals,
+    PlayVersion,
+    QualityReport,
+    Duplicates,
+    AnalyzeLoudness,
+    AnalyzeTempo,
+    BpmFilter,
+    IntegrityReport,
+    RenameMVs,
+    Stop,
+    PlayerControls,
+    ContinuousPlay,
+    AddToQueue,
+    Queue,
 }
 
 impl std::fmt::Display for MenuOptions {
@@ -29,18 +56,45 @@ impl std::fmt::Display for MenuOptions {
             MenuOptions::SortAsc => write!(f, "Sort Ascending"),
             MenuOptions::SortDesc => write!(f, "Sort Descending"),
             MenuOptions::SortMtime => write!(f, "Sort by Mtime"),
+            MenuOptions::SortReleaseDate => write!(f, "Sort by Release Date"),
+            MenuOptions::SortBpm => write!(f, "Sort by BPM"),
             MenuOptions::Random => write!(f, "Random"),
             MenuOptions::Quit => write!(f, "Quit"),
             MenuOptions::Update => write!(f, "Update"),
             MenuOptions::SearchFilter => write!(f, "Search Filter"),
             MenuOptions::ClearPlayed => write!(f, "Clear Played"),
+            MenuOptions::EditEntry => write!(f, "Edit Entry"),
+            MenuOptions::ToggleFavourites => write!(f, "Toggle Favourites"),
+            MenuOptions::TagFilter => write!(f, "Tag Filter"),
+            MenuOptions::RatingFilter => write!(f, "Rating Filter"),
+            MenuOptions::ArtistReport => write!(f, "Artist Report"),
+            MenuOptions::UnicodeReport => write!(f, "Unicode Report"),
+            MenuOptions::BrowseEras => write!(f, "Browse Eras"),
+            MenuOptions::OnThisDay => write!(f, "On This Day"),
+            MenuOptions::PlayNext => write!(f, "Play Next"),
+            MenuOptions::ToggleLyrics => write!(f, "Toggle Lyrics"),
+            MenuOptions::ToggleKaraoke => write!(f, "Toggle Karaoke"),
+            MenuOptions::DetectInstrumentals => write!(f, "Detect Instrumentals"),
+            MenuOptions::PlayVersion => write!(f, "Play Version"),
+            MenuOptions::QualityReport => write!(f, "Quality Report"),
+            MenuOptions::Duplicates => write!(f, "Duplicates"),
+            MenuOptions::AnalyzeLoudness => write!(f, "Analyze Loudness"),
+            MenuOptions::AnalyzeTempo => write!(f, "Analyze Tempo"),
+            MenuOptions::BpmFilter => write!(f, "BPM Filter"),
+            MenuOptions::IntegrityReport => write!(f, "Integrity Report"),
+            MenuOptions::RenameMVs => write!(f, "Rename MVs"),
+            MenuOptions::Stop => write!(f, "Stop"),
+            MenuOptions::PlayerControls => write!(f, "Player Controls"),
+            MenuOptions::ContinuousPlay => write!(f, "Continuous Play"),
+            MenuOptions::AddToQueue => write!(f, "Add to Queue"),
+            MenuOptions::Queue => write!(f, "Queue"),
         }
     }
 }
 
 impl MenuOptions {
     fn iterator() -> Iter<'static, MenuOptions> {
-        static OPTIONS: [MenuOptions; 12] = [
+        static OPTIONS: [MenuOptions; 39] = [
             MenuOptions::MainMenu,
             MenuOptions::MVSelector,
             MenuOptions::ToggleMVs,
@@ -48,11 +102,38 @@ impl MenuOptions {
             MenuOptions::SortAsc,
             MenuOptions::SortDesc,
             MenuOptions::SortMtime,
+            MenuOptions::SortReleaseDate,
+            MenuOptions::SortBpm,
             MenuOptions::Random,
             MenuOptions::Quit,
             MenuOptions::Update,
             MenuOptions::SearchFilter,
             MenuOptions::ClearPlayed,
+            MenuOptions::EditEntry,
+            MenuOptions::ToggleFavourites,
+            MenuOptions::TagFilter,
+            MenuOptions::RatingFilter,
+            MenuOptions::ArtistReport,
+            MenuOptions::UnicodeReport,
+            MenuOptions::BrowseEras,
+            MenuOptions::OnThisDay,
+            MenuOptions::PlayNext,
+            MenuOptions::ToggleLyrics,
+            MenuOptions::ToggleKaraoke,
+            MenuOptions::DetectInstrumentals,
+            MenuOptions::PlayVersion,
+            MenuOptions::QualityReport,
+            MenuOptions::Duplicates,
+            MenuOptions::AnalyzeLoudness,
+            MenuOptions::AnalyzeTempo,
+            MenuOptions::BpmFilter,
+            MenuOptions::IntegrityReport,
+            MenuOptions::RenameMVs,
+            MenuOptions::Stop,
+            MenuOptions::PlayerControls,
+            MenuOptions::ContinuousPlay,
+            MenuOptions::AddToQueue,
+            MenuOptions::Queue,
         ];
         OPTIONS.iter()
     }
@@ -1,8 +1,12 @@
+pub mod entry_editor;
 pub mod fzf_selector;
 pub mod menu;
 pub mod mv_selector;
+pub mod queue_view;
+pub mod report;
 pub mod updater;
 pub mod search_filter;
+pub mod tag_picker;
 
 use crossterm::{cursor, terminal, QueueableCommand};
 use std::io::{stdout, Write};
@@ -1,12 +1,26 @@
 use super::super::avmod::AudioVideoData;
 use super::clear_term;
+use super::entry_editor::{parse_stars, stars, EntryEditor};
 use super::fzf_selector::{FzfSelector, SelectType};
 use super::menu::MenuOptions;
+use super::queue_view::{QueueAction, QueueView};
+use super::report::ReportView;
+use crate::entry_meta::MAX_RATING;
+use crate::media_player::{PlaybackState, PlayerControl, PlayerEvent, PlayerStatus};
+use crate::tempo;
+use crate::unicode_paths;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use std::time::Duration;
 
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub enum FilterTypes {
     MVs,
     Live,
+    Favourites,
+    Tag(String),
+    MinRating(u8),
+    Bpm(u32, u32),
 }
 
 impl std::fmt::Display for FilterTypes {
@@ -14,6 +28,11 @@ impl std::fmt::Display for FilterTypes {
         match self {
             FilterTypes::MVs => write!(f, "MVs"),
             FilterTypes::Live => write!(f, "Live"),
+            FilterTypes::Favourites => write!(f, "Favourites"),
+            FilterTypes::Tag(tag) => write!(f, "Tag: {}", tag),
+            FilterTypes::MinRating(rating) => write!(f, "Rating: {}", stars(Some(*rating))),
+            FilterTypes::Bpm(min, u32::MAX) => write!(f, "bpm:{}-", min),
+            FilterTypes::Bpm(min, max) => write!(f, "bpm:{}-{}", min, max),
         }
     }
 }
@@ -24,6 +43,7 @@ pub struct MVSelector {
     header: String,
     filters: Vec<FilterTypes>,
     pub played_list: Vec<String>,
+    last_played: Option<String>,
 }
 
 /// UI Entrypoint
@@ -35,15 +55,35 @@ impl MVSelector {
             header: "Search for an MV or search quit to exit".to_owned(),
             filters: Vec::new(),
             played_list: Vec::new(),
+            last_played: None,
         }
     }
 
     pub async fn start(&mut self) -> MenuOptions {
         loop {
+            match self.avd.pending_player_events().pop() {
+                Some(PlayerEvent::Finished(video_path)) => {
+                    self.header = format!(
+                        "Finished {}\n\nSearch for an MV or search quit to exit",
+                        self.avd.video_name(&video_path)
+                    );
+                }
+                Some(PlayerEvent::Failed { video_path, error }) => {
+                    self.header = failure_header(&self.avd.video_name(&video_path), &error);
+                }
+                _ => {}
+            }
+            if let Some(notice) = self.avd.lyrics_notice.take() {
+                self.header = format!("{}\n{}", notice, self.header);
+            }
             clear_term(&self.header)
                 .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
             let menu = MenuOptions::generate_menu(vec![self.view_type.to_string()]);
-            let fzf_view = FzfSelector::new(Some(self.filtered_list()), Some(menu.clone()), None);
+            let video_list = self.filtered_list();
+            let search_keys = self.avd.search_keys(&video_list);
+            let fzf_view = FzfSelector::new(Some(video_list), Some(menu.clone()), None)
+                .with_search_keys(search_keys)
+                .with_preview(self.avd.preview_command());
             let selected = fzf_view.fzf_select(SelectType::Single);
             if selected.is_empty() {
                 return MenuOptions::Quit;
@@ -51,7 +91,10 @@ impl MVSelector {
             if let Some(view) = MenuOptions::get_selection(&selected) {
                 return view.clone();
             }
-            self.avd.play_media(&selected).await;
+            if !self.play(&selected, None).await {
+                continue;
+            }
+            self.last_played = Some(selected.to_owned());
             self.header = format!(
                 "Playing {}\n\nSearch for an MV or search quit to exit",
                 selected
@@ -59,6 +102,282 @@ impl MVSelector {
         }
     }
 
+    /// Play the video, or show why it can't be played in the header. Returns whether it plays
+    async fn play(&mut self, video_name: &str, version: Option<&str>) -> bool {
+        match self.avd.play_media_version(video_name, version).await {
+            Ok(()) => true,
+            Err(e) => {
+                self.header = failure_header(video_name, &e.to_string());
+                false
+            }
+        }
+    }
+
+    /// Play the video after the last played one in the filtered list, or the first one
+    pub async fn play_next(&mut self) -> MenuOptions {
+        let filtered_list = self.filtered_list();
+        let next_index = self
+            .last_played
+            .as_ref()
+            .and_then(|last| filtered_list.iter().position(|video| video == last))
+            .map(|index| index + 1)
+            .unwrap_or(0);
+        let Some(next_video) = filtered_list.get(next_index) else {
+            self.last_played = None;
+            self.header =
+                "Reached the end of the list\n\nSearch for an MV or search quit to exit".to_owned();
+            return MenuOptions::MVSelector;
+        };
+        self.last_played = Some(next_video.to_owned());
+        if !self.play(next_video, None).await {
+            return MenuOptions::MVSelector;
+        }
+        self.header = format!(
+            "Playing {} ({}/{})\n\nSearch for an MV or search quit to exit",
+            next_video,
+            next_index + 1,
+            filtered_list.len()
+        );
+        MenuOptions::MVSelector
+    }
+
+    /// Keep playing until Enter is pressed. When a track finishes the next one from the filtered
+    /// list, or a random one, is played
+    pub async fn continuous_play(&mut self) -> MenuOptions {
+        clear_term("Continuous play")
+            .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let fzf_view = FzfSelector::new(
+            None,
+            Some(vec![
+                "[[In Order]]".to_owned(),
+                "[[Random]]".to_owned(),
+                "[[Back]]".to_owned(),
+            ]),
+            None,
+        );
+        let random = match fzf_view.fzf_select(SelectType::Single).as_str() {
+            "[[In Order]]" => false,
+            "[[Random]]" => true,
+            _ => return MenuOptions::MVSelector,
+        };
+        self.avd.pending_player_events();
+        loop {
+            if self.avd.queue.has_next() {
+                self.play_queued(None).await;
+            } else if random {
+                self.play_random().await;
+                // The played list was full and got cleared
+                if self.avd.player_status() != PlayerStatus::Playing {
+                    self.play_random().await;
+                }
+            } else {
+                self.play_next().await;
+            }
+            if self.avd.player_status() != PlayerStatus::Playing {
+                return MenuOptions::MVSelector;
+            }
+            clear_term(&format!(
+                "Continuous play. Press Enter, Esc or q to stop\n\n{}",
+                self.header
+            ))
+            .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+            // Keys are read without waiting for a line so nothing is left reading stdin
+            // when fzf runs next
+            terminal::enable_raw_mode()
+                .unwrap_or_else(|e| eprintln!("Couldn't enable raw mode: {}", e));
+            let play_next = self.wait_for_finish().await;
+            terminal::disable_raw_mode()
+                .unwrap_or_else(|e| eprintln!("Couldn't disable raw mode: {}", e));
+            if !play_next {
+                return MenuOptions::MVSelector;
+            }
+        }
+    }
+
+    /// Wait until the video finishes or the stop key is pressed. Returns whether the next video
+    /// should be played
+    async fn wait_for_finish(&mut self) -> bool {
+        let mut key_check = tokio::time::interval(Duration::from_millis(100));
+        loop {
+            tokio::select! {
+                event = self.avd.next_player_event() => match event {
+                    Some(PlayerEvent::Finished(_)) => return true,
+                    Some(PlayerEvent::Stopped(_)) => continue,
+                    Some(PlayerEvent::Failed { video_path, error }) => {
+                        self.header = failure_header(&self.avd.video_name(&video_path), &error);
+                        return false;
+                    }
+                    None => return false,
+                },
+                _ = key_check.tick() => {
+                    if stop_key_pressed() {
+                        self.avd.stop_playback();
+                        self.header =
+                            "Stopped continuous play\n\nSearch for an MV or search quit to exit"
+                                .to_owned();
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Multi select MVs to add to the end of the queue
+    pub fn add_to_queue(&mut self) -> MenuOptions {
+        clear_term("Multi select MVs to add to the queue")
+            .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let video_list = self.filtered_list();
+        let search_keys = self.avd.search_keys(&video_list);
+        let fzf_view = FzfSelector::new(Some(video_list), None, None)
+            .with_search_keys(search_keys)
+            .with_preview(self.avd.preview_command());
+        let selected = fzf_view.fzf_select(SelectType::Multi);
+        let added = selected
+            .lines()
+            .filter(|video| !video.is_empty())
+            .inspect(|video| self.avd.queue.add(video))
+            .count();
+        if self.save_queue() {
+            self.header = format!(
+                "Added {} MVs to the queue\n\nSearch for an MV or search quit to exit",
+                added
+            );
+        }
+        MenuOptions::MVSelector
+    }
+
+    pub async fn show_queue(&mut self) -> MenuOptions {
+        let (queue, action) = QueueView::new(self.avd.queue.clone()).start();
+        self.avd.queue = queue;
+        if let QueueAction::Play(video_name) = action {
+            // Playing saves the queue
+            self.play_queued(Some(video_name)).await;
+        } else {
+            self.save_queue();
+        }
+        MenuOptions::MVSelector
+    }
+
+    /// Save the queue, or show why it couldn't be saved. Returns whether it was saved
+    fn save_queue(&mut self) -> bool {
+        match self.avd.save_queue() {
+            Ok(()) => true,
+            Err(e) => {
+                self.header = format!(
+                    "Couldn't save the queue: {}\n\nSearch for an MV or search quit to exit",
+                    e
+                );
+                false
+            }
+        }
+    }
+
+    /// Play the given queue entry, or the next one in the queue. Entries of videos that are no
+    /// longer in the library are skipped
+    async fn play_queued(&mut self, video_name: Option<String>) {
+        let video_name = match video_name {
+            Some(video_name) => video_name,
+            None => loop {
+                match self.avd.queue.next_entry() {
+                    Some(next) if self.avd.has_video(&next) => break next,
+                    Some(_) => continue,
+                    None => {
+                        self.save_queue();
+                        return;
+                    }
+                }
+            },
+        };
+        // The video still plays when the queue can't be saved, the header shows why
+        let saved = self.save_queue();
+        if !self.play(&video_name, None).await {
+            return;
+        }
+        self.last_played = Some(video_name.to_owned());
+        if !saved {
+            return;
+        }
+        self.header = format!(
+            "Playing {} ({}/{} in the queue)\n\nSearch for an MV or search quit to exit",
+            video_name,
+            self.avd.queue.position.map_or(0, |p| p + 1),
+            self.avd.queue.entries.len()
+        );
+    }
+
+    /// Select an MV and one of its audio versions, then play it or make it the default
+    pub async fn play_version(&mut self) -> MenuOptions {
+        clear_term("Select an MV to pick an audio version for")
+            .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let video_list = self.filtered_list();
+        let search_keys = self.avd.search_keys(&video_list);
+        let fzf_view = FzfSelector::new(Some(video_list), Some(vec!["[[Back]]".to_owned()]), None)
+            .with_search_keys(search_keys)
+            .with_preview(self.avd.preview_command());
+        let selected = fzf_view.fzf_select(SelectType::Single);
+        if selected.is_empty() || selected == "[[Back]]" {
+            return MenuOptions::MVSelector;
+        }
+        let versions = self.avd.audio_versions(&selected);
+        clear_term(&format!("Select an audio version for {}", selected))
+            .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let version_lines = versions
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let default = if i == 0 { " (default)" } else { "" };
+                format!("{}{} | {}", v.label, default, v.path)
+            })
+            .collect::<Vec<String>>();
+        let fzf_view = FzfSelector::new(
+            Some(version_lines.clone()),
+            Some(vec!["[[Back]]".to_owned()]),
+            None,
+        );
+        let selected_line = fzf_view.fzf_select(SelectType::Single);
+        let Some(version) = version_lines
+            .iter()
+            .position(|line| *line == selected_line)
+            .map(|i| versions[i].to_owned())
+        else {
+            return MenuOptions::MVSelector;
+        };
+        clear_term(&format!("{}: {}", selected, version.label))
+            .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let fzf_view = FzfSelector::new(
+            None,
+            Some(vec![
+                "[[Play]]".to_owned(),
+                "[[Make Default]]".to_owned(),
+                "[[Back]]".to_owned(),
+            ]),
+            None,
+        );
+        match fzf_view.fzf_select(SelectType::Single).as_str() {
+            "[[Play]]" => {
+                if !self.play(&selected, Some(&version.path)).await {
+                    return MenuOptions::MVSelector;
+                }
+                self.last_played = Some(selected.to_owned());
+                self.header = format!(
+                    "Playing {} ({})\n\nSearch for an MV or search quit to exit",
+                    selected, version.label
+                );
+            }
+            "[[Make Default]]" => {
+                self.header = match self.avd.set_default_version(&selected, &version.path) {
+                    Ok(()) => format!(
+                        "{} is now the default for {}\n\nSearch for an MV or search quit to exit",
+                        version.label, selected
+                    ),
+                    Err(e) => save_failure_header(&e),
+                };
+            }
+            _ => {}
+        }
+        MenuOptions::MVSelector
+    }
+
     pub async fn play_random(&mut self) -> MenuOptions {
         let filtered_list = self
             .filtered_list()
@@ -71,11 +390,12 @@ impl MVSelector {
             self.header = "No more videos to play\n\nClearing played list".to_owned();
             return MenuOptions::MVSelector;
         }
-        let random_video = filtered_list
-            .get(rand::random::<usize>() % filtered_list.len())
-            .unwrap();
+        let random_video = self.weighted_random(&filtered_list);
         self.played_list.push(random_video.to_owned());
-        self.avd.play_media(random_video).await;
+        self.last_played = Some(random_video.to_owned());
+        if !self.play(random_video, None).await {
+            return MenuOptions::MVSelector;
+        }
         self.header = format!(
             "Playing {}\nPlayed {} videos\n\nSearch for an MV or search quit to exit. ",
             random_video,
@@ -84,11 +404,34 @@ impl MVSelector {
         MenuOptions::MVSelector
     }
 
+    /// Pick a random video, favourites and higher rated videos are picked more often
+    fn weighted_random<'a>(&self, videos: &'a [String]) -> &'a String {
+        let weights = videos
+            .iter()
+            .map(|video| self.avd.entry_meta(video).random_weight())
+            .collect::<Vec<u32>>();
+        let total = weights.iter().sum::<u32>();
+        if total == 0 {
+            return &videos[rand::random::<usize>() % videos.len()];
+        }
+        let mut pick = rand::random::<u32>() % total;
+        for (video, weight) in videos.iter().zip(weights) {
+            if pick < weight {
+                return video;
+            }
+            pick -= weight;
+        }
+        videos.last().unwrap()
+    }
+
     pub fn filtered_list(&mut self) -> Vec<String> {
         self.avd
             .list_videos()
             .iter()
             .filter(|video| {
+                if !self.matches_meta_filters(video) {
+                    return false;
+                }
                 let mv_name = video.split(" - ").last();
                 if mv_name.is_none() {
                     return false;
@@ -106,6 +449,19 @@ impl MVSelector {
             .collect()
     }
 
+    fn matches_meta_filters(&self, video: &str) -> bool {
+        let meta = self.avd.entry_meta(video);
+        self.filters.iter().all(|filter| match filter {
+            FilterTypes::Favourites => meta.favourite,
+            FilterTypes::Tag(tag) => meta.tags.contains(tag),
+            FilterTypes::MinRating(rating) => meta.rating.unwrap_or(0) >= *rating,
+            FilterTypes::Bpm(min, max) => meta
+                .bpm
+                .is_some_and(|bpm| tempo::in_range(bpm, (*min, *max))),
+            FilterTypes::MVs | FilterTypes::Live => true,
+        })
+    }
+
     pub fn toggle_filter(&mut self, filter: FilterTypes) -> MenuOptions {
         if self.filters.contains(&filter) {
             self.filters.retain(|f| *f != filter);
@@ -115,7 +471,570 @@ impl MVSelector {
         MenuOptions::MVSelector
     }
 
+    pub fn stop(&mut self) -> MenuOptions {
+        self.header = match self.avd.player_status() {
+            PlayerStatus::Playing => {
+                self.avd.stop_playback();
+                "Stopped playback\n\nSearch for an MV or search quit to exit".to_owned()
+            }
+            _ => "Nothing is playing\n\nSearch for an MV or search quit to exit".to_owned(),
+        };
+        MenuOptions::MVSelector
+    }
+
+    /// Pause, seek and change the volume of the running player until going back
+    pub fn player_controls(&mut self) -> MenuOptions {
+        let mut message = String::new();
+        loop {
+            let state = match self.avd.playback_state() {
+                Some(state) => playback_line(&state),
+                None => "No position from the player".to_owned(),
+            };
+            clear_term(&format!("{}\n{}", state, message))
+                .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+            let options = [
+                "[[Pause/Resume]]",
+                "[[Seek +10s]]",
+                "[[Seek -10s]]",
+                "[[Seek +60s]]",
+                "[[Seek -60s]]",
+                "[[Volume +5]]",
+                "[[Volume -5]]",
+                "[[Refresh]]",
+                "[[Stop]]",
+                "[[Back]]",
+            ];
+            let fzf_view = FzfSelector::new(
+                None,
+                Some(options.iter().map(|o| o.to_string()).collect()),
+                None,
+            );
+            let control = match fzf_view.fzf_select(SelectType::Single).as_str() {
+                "[[Pause/Resume]]" => PlayerControl::TogglePause,
+                "[[Seek +10s]]" => PlayerControl::Seek(10.0),
+                "[[Seek -10s]]" => PlayerControl::Seek(-10.0),
+                "[[Seek +60s]]" => PlayerControl::Seek(60.0),
+                "[[Seek -60s]]" => PlayerControl::Seek(-60.0),
+                "[[Volume +5]]" => PlayerControl::Volume(5.0),
+                "[[Volume -5]]" => PlayerControl::Volume(-5.0),
+                "[[Refresh]]" => {
+                    message.clear();
+                    continue;
+                }
+                "[[Stop]]" => return self.stop(),
+                _ => return MenuOptions::MVSelector,
+            };
+            message = match self.avd.control_player(control) {
+                Ok(()) => String::new(),
+                Err(e) => format!("Couldn't control the player: {}", e),
+            };
+        }
+    }
+
+    pub fn toggle_lyrics(&mut self) -> MenuOptions {
+        self.avd.show_lyrics = !self.avd.show_lyrics;
+        self.header = format!(
+            "Lyrics {}\n\nSearch for an MV or search quit to exit",
+            if self.avd.show_lyrics { "on" } else { "off" }
+        );
+        MenuOptions::MVSelector
+    }
+
+    pub fn toggle_karaoke(&mut self) -> MenuOptions {
+        self.avd.karaoke = !self.avd.karaoke;
+        self.header = format!(
+            "Karaoke {}\n\nSearch for an MV or search quit to exit",
+            if self.avd.karaoke { "on" } else { "off" }
+        );
+        MenuOptions::MVSelector
+    }
+
+    pub fn detect_instrumentals(&mut self) -> MenuOptions {
+        self.header = match self.avd.detect_instrumentals() {
+            Ok(found) => format!(
+                "Found instrumentals for {} MVs\n\nSearch for an MV or search quit to exit",
+                found
+            ),
+            Err(e) => save_failure_header(&e),
+        };
+        MenuOptions::MVSelector
+    }
+
+    pub fn analyze_tempo(&mut self) -> MenuOptions {
+        self.header = match self.avd.analyze_tempo() {
+            Ok(analyzed) => format!(
+                "Analyzed the tempo of {} entries\n\nSearch for an MV or search quit to exit",
+                analyzed
+            ),
+            Err(e) => save_failure_header(&e),
+        };
+        MenuOptions::MVSelector
+    }
+
+    pub fn analyze_loudness(&mut self) -> MenuOptions {
+        self.header = match self.avd.analyze_loudness() {
+            Ok(analyzed) => format!(
+                "Analyzed the loudness of {} entries\n\nSearch for an MV or search quit to exit",
+                analyzed
+            ),
+            Err(e) => save_failure_header(&e),
+        };
+        MenuOptions::MVSelector
+    }
+
+    /// Save the links and the meta, or show why they couldn't be saved
+    pub fn save_data(&mut self) {
+        if let Err(e) = self.avd.save_data() {
+            self.header = save_failure_header(&e);
+        }
+    }
+
     pub fn set_search_filters(&mut self, new_list: Option<Vec<String>>) {
         self.avd.search_filtered_list = new_list;
     }
+
+    /// Select an MV and edit its tags, rating and favourite flag
+    pub fn edit_entry(&mut self) -> MenuOptions {
+        clear_term("Select an MV to edit")
+            .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let video_list = self.filtered_list();
+        let search_keys = self.avd.search_keys(&video_list);
+        let fzf_view = FzfSelector::new(Some(video_list), Some(vec!["[[Back]]".to_owned()]), None)
+            .with_search_keys(search_keys);
+        let selected = fzf_view.fzf_select(SelectType::Single);
+        if selected.is_empty() || selected == "[[Back]]" {
+            return MenuOptions::MVSelector;
+        }
+        let editor = EntryEditor::new(
+            selected.to_owned(),
+            self.avd.entry_meta(&selected),
+            self.avd.all_tags(),
+        );
+        let meta = editor.start();
+        self.avd.update_meta(&selected, |m| *m = meta);
+        self.save_data();
+        self.avd.video_list = None;
+        MenuOptions::MVSelector
+    }
+
+    /// Select the tags to filter by. Selecting nothing clears the tag filters
+    pub fn choose_tag_filter(&mut self) -> MenuOptions {
+        clear_term("Multi Select tags to filter by. Select nothing to clear tag filters")
+            .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let tags = self.avd.all_tags().into_iter().collect::<Vec<String>>();
+        let fzf_view = FzfSelector::new(Some(tags), None, None);
+        let selected = fzf_view.fzf_select(SelectType::Multi);
+        self.filters.retain(|f| !matches!(f, FilterTypes::Tag(_)));
+        for tag in selected.split('\n').filter(|tag| !tag.is_empty()) {
+            self.filters.push(FilterTypes::Tag(tag.to_owned()));
+        }
+        MenuOptions::MVSelector
+    }
+
+    /// Select the minimum rating to filter by
+    pub fn choose_rating_filter(&mut self) -> MenuOptions {
+        clear_term("Select the minimum rating")
+            .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let mut options = (1..=MAX_RATING)
+            .rev()
+            .map(|r| stars(Some(r)))
+            .collect::<Vec<String>>();
+        options.push("Any".to_owned());
+        let fzf_view = FzfSelector::new(Some(options), None, None);
+        let selected = fzf_view.fzf_select(SelectType::Single);
+        if selected.is_empty() {
+            return MenuOptions::MVSelector;
+        }
+        self.filters
+            .retain(|f| !matches!(f, FilterTypes::MinRating(_)));
+        if let Some(rating) = parse_stars(&selected) {
+            self.filters.push(FilterTypes::MinRating(rating));
+        }
+        MenuOptions::MVSelector
+    }
+
+    /// Select or type a BPM range like bpm:120-130 to filter by
+    pub fn choose_bpm_filter(&mut self) -> MenuOptions {
+        clear_term("Select or type a BPM range like bpm:120-130, bpm:120- or bpm:-100")
+            .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let mut options = [(60, 90), (90, 110), (110, 130), (130, 150), (150, 200)]
+            .iter()
+            .map(|(min, max)| FilterTypes::Bpm(*min, *max).to_string())
+            .collect::<Vec<String>>();
+        options.push("Any".to_owned());
+        let fzf_view = FzfSelector::new(Some(options), None, None);
+        let (query, selected) = fzf_view.fzf_select_with_query(SelectType::Single);
+        // A typed range wins over the option it happens to fuzzy match
+        let range = tempo::parse_bpm_range(&query)
+            .or_else(|| selected.first().and_then(|s| tempo::parse_bpm_range(s)));
+        if range.is_none() && selected.is_empty() {
+            return MenuOptions::MVSelector;
+        }
+        self.filters
+            .retain(|f| !matches!(f, FilterTypes::Bpm(_, _)));
+        if let Some((min, max)) = range {
+            self.filters.push(FilterTypes::Bpm(min, max));
+        }
+        MenuOptions::MVSelector
+    }
+
+    /// Show artists that are not in the alias table but look like a variant of a known artist
+    pub fn artist_report(&mut self) -> MenuOptions {
+        let video_names = self.avd.list_videos();
+        let raw_artists = video_names
+            .iter()
+            .map(|name| self.avd.parsed_name(name).raw_artist)
+            .collect::<Vec<String>>();
+        let lines = self
+            .avd
+            .aliases
+            .unknown_variants(raw_artists.iter().map(|a| a.as_str()))
+            .into_iter()
+            .map(|(unknown, canonical)| format!("{} looks like {}", unknown, canonical))
+            .collect();
+        ReportView::new(
+            "Unknown artists that look like variants of known artists".to_owned(),
+            lines,
+        )
+        .start();
+        MenuOptions::MVSelector
+    }
+
+    /// Show file names that exist in both NFC and NFD form in the video or audio directory
+    pub fn unicode_report(&mut self) -> MenuOptions {
+        let lines = [&self.avd.video_dir, &self.avd.audio_dir]
+            .iter()
+            .flat_map(|dir| unicode_paths::mixed_forms(dir))
+            .map(|(name, forms)| format!("{} exists in {} forms", name, forms.len()))
+            .collect();
+        ReportView::new(
+            "Names that exist in more than one unicode normalization form".to_owned(),
+            lines,
+        )
+        .start();
+        MenuOptions::MVSelector
+    }
+
+    /// Audit the links against the files on disk
+    pub fn integrity_report(&mut self) -> MenuOptions {
+        let report = self.avd.integrity_report(true);
+        ReportView::new(
+            format!("{} problems found in the library", report.issue_count()),
+            report.table(),
+        )
+        .start();
+        MenuOptions::MVSelector
+    }
+
+    /// Show the names the rename template gives the filtered list as a dry run, then rename the
+    /// selected videos
+    pub fn rename_mvs(&mut self) -> MenuOptions {
+        let video_list = self.filtered_list();
+        let (renames, conflicts) = self.avd.rename_plan(&video_list);
+        let name = |path: &str| self.avd.video_name(path);
+        let lines = renames
+            .iter()
+            .map(|r| format!("{} -> {}", name(&r.from), name(&r.to)))
+            .collect::<Vec<String>>();
+        let conflict_lines = conflicts
+            .iter()
+            .map(|(r, reason)| {
+                format!(
+                    "[conflict] {} -> {}: {}",
+                    name(&r.from),
+                    name(&r.to),
+                    reason
+                )
+            })
+            .collect::<Vec<String>>();
+        clear_term(&format!(
+            "{} videos would be renamed, {} conflicts. Multi select the renames to do",
+            renames.len(),
+            conflicts.len()
+        ))
+        .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let mut options = conflict_lines;
+        options.push("[[Back]]".to_owned());
+        if !renames.is_empty() {
+            options.insert(0, format!("[[Rename All {}]]", renames.len()));
+        }
+        let fzf_view = FzfSelector::new(Some(lines.clone()), Some(options), None);
+        let selected = fzf_view.fzf_select(SelectType::Multi);
+        let rename_all = selected.starts_with("[[Rename All");
+        let chosen = renames
+            .into_iter()
+            .zip(lines.iter())
+            .filter(|(_, line)| rename_all || selected.lines().any(|s| s == line.as_str()))
+            .map(|(rename, _)| rename)
+            .collect::<Vec<_>>();
+        if chosen.is_empty() {
+            return MenuOptions::MVSelector;
+        }
+        self.header = match self.avd.rename_videos(&chosen) {
+            Ok(renamed) => format!(
+                "Renamed {} videos\n\nSearch for an MV or search quit to exit",
+                renamed
+            ),
+            Err(e) => format!("{}\n\nSearch for an MV or search quit to exit", e),
+        };
+        self.forget_missing_videos();
+        MenuOptions::MVSelector
+    }
+
+    /// Group the filtered list by release year or by artist era and show the selected group
+    pub fn browse_eras(&mut self) -> MenuOptions {
+        clear_term("Browse by").unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let fzf_view = FzfSelector::new(
+            None,
+            Some(vec![
+                "[[By Year]]".to_owned(),
+                "[[By Artist Era]]".to_owned(),
+                "[[Back]]".to_owned(),
+            ]),
+            None,
+        );
+        let by_artist = match fzf_view.fzf_select(SelectType::Single).as_str() {
+            "[[By Year]]" => false,
+            "[[By Artist Era]]" => true,
+            _ => return MenuOptions::MVSelector,
+        };
+        self.set_search_filters(None);
+        let video_list = self.filtered_list();
+        let groups = self.avd.group_by_era(&video_list, by_artist);
+        clear_term("Select an era").unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let group_lines = groups
+            .iter()
+            .map(|(era, videos)| format!("{} ({})", era, videos.len()))
+            .collect::<Vec<String>>();
+        let fzf_view = FzfSelector::new(Some(group_lines), Some(vec!["[[Back]]".to_owned()]), None);
+        let selected = fzf_view.fzf_select(SelectType::Single);
+        let era = selected
+            .rsplit_once(" (")
+            .map(|(era, _)| era)
+            .unwrap_or_default();
+        if let Some(videos) = groups.get(era) {
+            self.set_search_filters(Some(videos.to_owned()));
+            self.header = format!("Showing {}\n\nSearch for an MV or search quit to exit", era);
+        }
+        MenuOptions::MVSelector
+    }
+
+    /// Show the MVs released on today's date in earlier years, ordered by year
+    pub fn on_this_day(&mut self) -> MenuOptions {
+        self.set_search_filters(None);
+        let video_list = self.filtered_list();
+        let (videos, widened) = self.avd.on_this_day(&video_list);
+        if videos.is_empty() {
+            self.header = "No MVs were released this week in earlier years\n\nSearch for an MV or search quit to exit".to_owned();
+            return MenuOptions::MVSelector;
+        }
+        self.header = format!(
+            "{} MVs released {} in earlier years\n\nSearch for an MV or search quit to exit",
+            videos.len(),
+            if widened { "this week" } else { "on this day" }
+        );
+        self.set_search_filters(Some(videos));
+        MenuOptions::MVSelector
+    }
+
+    /// Show entries on lossy audio and offer to relink the ones that have a better copy
+    pub fn quality_report(&mut self) -> MenuOptions {
+        let report = self.avd.quality_report();
+        let lines = report
+            .iter()
+            .map(|entry| match &entry.better_copy {
+                Some(better) => format!(
+                    "[better copy] {} | {} -> {}",
+                    entry.video_name, entry.audio_path, better
+                ),
+                None => format!("[lossy] {} | {}", entry.video_name, entry.audio_path),
+            })
+            .collect::<Vec<String>>();
+        let relinkable = report
+            .iter()
+            .filter(|entry| entry.better_copy.is_some())
+            .count();
+        clear_term(&format!(
+            "{} entries on lossy audio or with a better copy. Multi select entries to relink \
+             to their better copy",
+            report.len()
+        ))
+        .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let mut options = vec!["[[Back]]".to_owned()];
+        if relinkable > 0 {
+            options.insert(0, format!("[[Relink All {}]]", relinkable));
+        }
+        let fzf_view = FzfSelector::new(Some(lines.clone()), Some(options), None);
+        let selected = fzf_view.fzf_select(SelectType::Multi);
+        let relink_all = selected.starts_with("[[Relink All");
+        let mut relinked = 0;
+        for (entry, line) in report.iter().zip(lines.iter()) {
+            let Some(better) = &entry.better_copy else {
+                continue;
+            };
+            if relink_all || selected.lines().any(|s| s == line) {
+                self.avd.relink(&entry.video_name, better);
+                relinked += 1;
+            }
+        }
+        if relinked > 0 {
+            self.header = format!(
+                "Relinked {} entries to a better copy\n\nSearch for an MV or search quit to exit",
+                relinked
+            );
+            self.save_data();
+        }
+        MenuOptions::MVSelector
+    }
+
+    /// Go through the groups of duplicate MVs, pick the preferred copy of each and hide or trash
+    /// the others
+    pub fn duplicates(&mut self) -> MenuOptions {
+        let groups = self.avd.duplicate_groups();
+        let mut handled = 0;
+        let mut errors = Vec::new();
+        for (i, group) in groups.iter().enumerate() {
+            let last_error = errors
+                .last()
+                .map(|e| format!("\n{}", e))
+                .unwrap_or_default();
+            clear_term(&format!(
+                "Duplicate group {} of {}. Select the preferred copy{}",
+                i + 1,
+                groups.len(),
+                last_error
+            ))
+            .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+            let lines = group
+                .iter()
+                .map(|video| {
+                    let duration = video
+                        .duration
+                        .map(|d| format!("{}:{:02}", d as u64 / 60, d as u64 % 60))
+                        .unwrap_or("?".to_owned());
+                    format!(
+                        "{} | {} | {}",
+                        self.avd.video_name(&video.path),
+                        video.resolution(),
+                        duration
+                    )
+                })
+                .collect::<Vec<String>>();
+            let fzf_view = FzfSelector::new(
+                Some(lines.clone()),
+                Some(vec!["[[Skip]]".to_owned(), "[[Back]]".to_owned()]),
+                None,
+            );
+            let selected = fzf_view.fzf_select(SelectType::Single);
+            if selected.is_empty() || selected == "[[Back]]" {
+                break;
+            }
+            let Some(keep) = lines.iter().position(|line| *line == selected) else {
+                continue;
+            };
+            let others = group
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != keep)
+                .map(|(_, video)| video.path.to_owned())
+                .collect::<Vec<String>>();
+            clear_term("What should happen to the other copies?")
+                .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+            let fzf_view = FzfSelector::new(
+                None,
+                Some(vec![
+                    "[[Hide Others]]".to_owned(),
+                    format!("[[Move Others to {}]]", self.avd.trash_dir),
+                    "[[Skip]]".to_owned(),
+                ]),
+                None,
+            );
+            let action = fzf_view.fzf_select(SelectType::Single);
+            if action == "[[Hide Others]]" {
+                match self.avd.hide_duplicates(&group[keep].path, &others) {
+                    Ok(()) => handled += 1,
+                    Err(e) => errors.push(format!("Couldn't hide duplicates: {}", e)),
+                }
+            } else if action.starts_with("[[Move Others") {
+                match self.avd.trash_videos(&others) {
+                    Ok(_) => handled += 1,
+                    Err(e) => errors.push(format!("Couldn't move duplicates to trash: {}", e)),
+                }
+            }
+        }
+        self.header = format!(
+            "Handled {} of {} duplicate groups\n{}\nSearch for an MV or search quit to exit",
+            handled,
+            groups.len(),
+            errors
+                .iter()
+                .map(|e| format!("{}\n", e))
+                .collect::<String>()
+        );
+        self.forget_missing_videos();
+        MenuOptions::MVSelector
+    }
+
+    /// Drop videos that were renamed or removed from the played list. The queue, the meta and
+    /// the player are updated by `AudioVideoData` when the videos are moved
+    fn forget_missing_videos(&mut self) {
+        self.played_list.retain(|video| self.avd.has_video(video));
+        if !self
+            .last_played
+            .as_ref()
+            .is_some_and(|video| self.avd.has_video(video))
+        {
+            self.last_played = None;
+        }
+    }
+}
+
+/// Read the pending key presses without blocking. Enter, Esc, q and Ctrl-C stop
+fn stop_key_pressed() -> bool {
+    let mut pressed = false;
+    while event::poll(Duration::ZERO).unwrap_or(false) {
+        if let Ok(Event::Key(key)) = event::read() {
+            pressed |= key.kind == KeyEventKind::Press
+                && (matches!(key.code, KeyCode::Enter | KeyCode::Esc | KeyCode::Char('q'))
+                    || (key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(KeyModifiers::CONTROL)));
+        }
+    }
+    pressed
+}
+
+/// Header after the data or meta file could not be written
+fn save_failure_header(error: &std::io::Error) -> String {
+    format!(
+        "Couldn't save the library: {}\n\nSearch for an MV or search quit to exit",
+        error
+    )
+}
+
+/// Header after a player could not be started or exited with an error
+fn failure_header(video_name: &str, error: &str) -> String {
+    format!(
+        "Couldn't play {}\n{}\n\nSearch for an MV or search quit to exit",
+        video_name, error
+    )
+}
+
+/// Position, duration, volume and whether the player is paused, for example "▶ 1:05 / 3:20"
+fn playback_line(state: &PlaybackState) -> String {
+    let time = |seconds: f64| {
+        let seconds = seconds.max(0.0) as u64;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    };
+    let mut line = format!(
+        "{} {}",
+        if state.paused { "⏸" } else { "▶" },
+        time(state.position)
+    );
+    if let Some(duration) = state.duration {
+        line.push_str(&format!(" / {}", time(duration)));
+    }
+    if let Some(volume) = state.volume {
+        line.push_str(&format!("  Volume {:.0}%", volume));
+    }
+    line
 }
@@ -0,0 +1,130 @@
+use crate::play_queue::PlayQueue;
+use crate::views::clear_term;
+
+use super::fzf_selector::{FzfSelector, SelectType};
+
+/// Reorder, remove and pick entries of the play queue
+pub struct QueueView {
+    queue: PlayQueue,
+}
+
+/// What to do once the queue view is left
+#[derive(Debug, PartialEq, Eq)]
+pub enum QueueAction {
+    Back,
+    /// Play the entry at the queue position
+    Play(String),
+}
+
+impl QueueView {
+    pub fn new(queue: PlayQueue) -> Self {
+        Self { queue }
+    }
+
+    /// Returns the edited queue and whether an entry should be played
+    pub fn start(mut self) -> (PlayQueue, QueueAction) {
+        loop {
+            clear_term(&format!(
+                "Queue: {} entries. Select an entry to play it",
+                self.queue.entries.len()
+            ))
+            .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+            let fzf_view = FzfSelector::new(
+                Some(self.lines()),
+                Some(vec![
+                    "[[Play Next]]".to_owned(),
+                    "[[Play Previous]]".to_owned(),
+                    "[[Move Up]]".to_owned(),
+                    "[[Move Down]]".to_owned(),
+                    "[[Remove]]".to_owned(),
+                    "[[Clear]]".to_owned(),
+                    "[[Back]]".to_owned(),
+                ]),
+                None,
+            );
+            let selected = fzf_view.fzf_select(SelectType::Single);
+            let played = match selected.as_str() {
+                "[[Play Next]]" => self.queue.next_entry(),
+                "[[Play Previous]]" => self.queue.previous_entry(),
+                "[[Move Up]]" => {
+                    if let Some(index) = self.pick_entries("Select an entry to move up").first() {
+                        self.queue.move_entry(*index, index.saturating_sub(1));
+                    }
+                    None
+                }
+                "[[Move Down]]" => {
+                    if let Some(index) = self.pick_entries("Select an entry to move down").first() {
+                        let last = self.queue.entries.len().saturating_sub(1);
+                        self.queue.move_entry(*index, (index + 1).min(last));
+                    }
+                    None
+                }
+                "[[Remove]]" => {
+                    let indices = self.pick_entries("Multi select the entries to remove");
+                    self.queue.remove(&indices);
+                    None
+                }
+                "[[Clear]]" => {
+                    self.queue.clear();
+                    None
+                }
+                "" | "[[Back]]" => return (self.queue, QueueAction::Back),
+                line => line_index(line).and_then(|index| self.queue.jump(index)),
+            };
+            if let Some(video_name) = played {
+                return (self.queue, QueueAction::Play(video_name));
+            }
+        }
+    }
+
+    /// Numbered entries, the last played one is marked
+    fn lines(&self) -> Vec<String> {
+        self.queue
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let marker = if self.queue.position == Some(i) {
+                    "▶"
+                } else {
+                    " "
+                };
+                format!("{} {}. {}", marker, i + 1, entry)
+            })
+            .collect()
+    }
+
+    fn pick_entries(&self, header: &str) -> Vec<usize> {
+        clear_term(header).unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let fzf_view = FzfSelector::new(Some(self.lines()), None, None);
+        fzf_view
+            .fzf_select(SelectType::Multi)
+            .lines()
+            .filter_map(line_index)
+            .collect()
+    }
+}
+
+/// Queue index of a line from `QueueView::lines`
+fn line_index(line: &str) -> Option<usize> {
+    let (number, _) = line.trim_start_matches(['▶', ' ']).split_once(". ")?;
+    number.parse::<usize>().ok()?.checked_sub(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_index() {
+        let view = QueueView::new(PlayQueue {
+            entries: vec!["IU - Blueming.mp4".to_owned(), "1. Intro.mp4".to_owned()],
+            position: Some(1),
+        });
+        let lines = view.lines();
+        assert_eq!(lines[1], "▶ 2. 1. Intro.mp4");
+        assert_eq!(line_index(&lines[0]), Some(0));
+        assert_eq!(line_index(&lines[1]), Some(1));
+        assert_eq!(line_index("[[Back]]"), None);
+    }
+}
@@ -0,0 +1,32 @@
+use crate::views::clear_term;
+
+use super::fzf_selector::{FzfSelector, SelectType};
+
+/// Show the lines of a report in fzf until the user goes back
+pub struct ReportView {
+    header: String,
+    lines: Vec<String>,
+}
+
+impl ReportView {
+    pub fn new(header: String, lines: Vec<String>) -> Self {
+        Self { header, lines }
+    }
+
+    pub fn start(&self) {
+        loop {
+            clear_term(&self.header)
+                .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+            let lines = if self.lines.is_empty() {
+                vec!["Nothing to report".to_owned()]
+            } else {
+                self.lines.clone()
+            };
+            let fzf_view = FzfSelector::new(Some(lines), Some(vec!["[[Back]]".to_owned()]), None);
+            let selected = fzf_view.fzf_select(SelectType::Single);
+            if selected.is_empty() || selected == "[[Back]]" {
+                return;
+            }
+        }
+    }
+}
@@ -1,14 +1,19 @@
 use crate::views::clear_term;
 
 use super::fzf_selector::{FzfSelector, SelectType};
+use std::collections::HashMap;
 
 pub struct SearchFilters {
     pub video_list: Vec<String>,
+    pub search_keys: HashMap<String, String>,
 }
 
 impl SearchFilters {
-    pub fn new(video_list: Vec<String>) -> Self {
-        Self { video_list }
+    pub fn new(video_list: Vec<String>, search_keys: HashMap<String, String>) -> Self {
+        Self {
+            video_list,
+            search_keys,
+        }
     }
 
     pub fn start(&mut self) -> Vec<String> {
@@ -17,7 +22,8 @@ impl SearchFilters {
             Some(self.video_list.clone()),
             Some(vec!["[[Back]]".to_owned()]),
             None,
-        );
+        )
+        .with_search_keys(self.search_keys.clone());
         let selections = fzf_view.fzf_select(SelectType::Multi);
         selections
             .split('\n')
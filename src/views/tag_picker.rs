@@ -0,0 +1,85 @@
+use crate::views::clear_term;
+
+use super::fzf_selector::{FzfSelector, SelectType};
+use std::collections::BTreeSet;
+
+/// Multi select over the existing tags. Selected tags are toggled on the entry and any text typed
+/// that does not match an existing tag is added as new tags, split on commas
+pub struct TagPicker {
+    pub all_tags: BTreeSet<String>,
+    pub current_tags: BTreeSet<String>,
+}
+
+impl TagPicker {
+    pub fn new(all_tags: BTreeSet<String>, current_tags: BTreeSet<String>) -> Self {
+        Self {
+            all_tags,
+            current_tags,
+        }
+    }
+
+    /// Returns the tags that should be toggled on the entry
+    pub fn start(&self) -> Vec<String> {
+        clear_term(
+            "Select tags to toggle ([x] = set). Type a new tag and press enter to add it, \
+             separate multiple new tags with commas",
+        )
+        .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let tag_list = self
+            .all_tags
+            .union(&self.current_tags)
+            .map(|tag| self.tag_line(tag))
+            .collect::<Vec<String>>();
+        let fzf_view = FzfSelector::new(Some(tag_list), Some(vec!["[[Back]]".to_owned()]), None);
+        let (query, selected) = fzf_view.fzf_select_with_query(SelectType::Multi);
+        Self::parse_selection(&query, &selected)
+    }
+
+    fn tag_line(&self, tag: &str) -> String {
+        if self.current_tags.contains(tag) {
+            format!("[x] {}", tag)
+        } else {
+            format!("[ ] {}", tag)
+        }
+    }
+
+    fn parse_selection(query: &str, selected: &[String]) -> Vec<String> {
+        if selected.iter().any(|s| s == "[[Back]]") {
+            return Vec::new();
+        }
+        if selected.is_empty() {
+            return query
+                .split(',')
+                .map(|tag| tag.trim().to_owned())
+                .filter(|tag| !tag.is_empty())
+                .collect();
+        }
+        selected
+            .iter()
+            .map(|line| {
+                line.trim_start_matches("[x] ")
+                    .trim_start_matches("[ ] ")
+                    .to_owned()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_selection() {
+        let selected = vec!["[x] choreo".to_string(), "[ ] 2nd gen".to_string()];
+        assert_eq!(
+            TagPicker::parse_selection("cho", &selected),
+            vec!["choreo".to_string(), "2nd gen".to_string()]
+        );
+        assert_eq!(
+            TagPicker::parse_selection("broken-sync, live ", &[]),
+            vec!["broken-sync".to_string(), "live".to_string()]
+        );
+        assert!(TagPicker::parse_selection("", &["[[Back]]".to_string()]).is_empty());
+    }
+}
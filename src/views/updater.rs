@@ -1,8 +1,11 @@
+use crate::entry_meta::MetaStore;
+use crate::quality::{self, QualityPolicy};
+use crate::unicode_paths;
 use crate::views::clear_term;
 
 use super::fzf_selector::{FzfSelector, SelectType};
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use std::sync::Arc;
 use walkdir::WalkDir;
@@ -15,20 +18,29 @@ pub struct Updater {
     mv_dir: String,
     audio_dir: String,
     audio_video: Arc<RefCell<JsonFormat>>,
+    meta: Arc<RefCell<MetaStore>>,
     mvs_found: Option<Vec<String>>,
     audio_found: Option<Vec<String>>,
     selected_mv: Option<String>,
+    pub quality_policy: QualityPolicy,
 }
 
 impl Updater {
-    pub fn new(mv_dir: String, audio_dir: String, audio_video: Arc<RefCell<JsonFormat>>) -> Self {
+    pub fn new(
+        mv_dir: String,
+        audio_dir: String,
+        audio_video: Arc<RefCell<JsonFormat>>,
+        meta: Arc<RefCell<MetaStore>>,
+    ) -> Self {
         Self {
             mv_dir,
             audio_dir,
             audio_video,
+            meta,
             mvs_found: None,
             audio_found: None,
             selected_mv: None,
+            quality_policy: QualityPolicy::default(),
         }
     }
 
@@ -37,7 +49,7 @@ impl Updater {
             if self.mvs_found.is_none() {
                 self.scan_mvs();
             }
-            if self.mvs_found.as_ref().unwrap().is_empty() {
+            if self.mvs_found.as_ref().unwrap().is_empty() && self.audio_video.borrow().is_empty() {
                 return MenuOptions::MVSelector;
             }
             if self.audio_found.is_none() {
@@ -51,16 +63,24 @@ impl Updater {
             let mv_list = self.mvs_found.as_ref().unwrap();
             let fzf_view = FzfSelector::new(
                 Some(mv_list.clone()),
-                Some(vec!["[[Back]]".to_owned()]),
+                Some(vec!["[[Add Version]]".to_owned(), "[[Back]]".to_owned()]),
                 None,
             );
             self.selected_mv = None;
             self.selected_mv = Some(fzf_view.fzf_select(SelectType::Single));
-            if self.selected_mv.as_ref().unwrap() == "[[Back]]" {
-                return MenuOptions::MVSelector;
+            match self.selected_mv.as_deref().unwrap() {
+                "[[Back]]" | "" => return MenuOptions::MVSelector,
+                "[[Add Version]]" => {
+                    self.add_version();
+                    continue;
+                }
+                _ => {}
             }
             let list_audios = ListAudios::new(audio_list.clone());
-            let selected_audio = list_audios.start();
+            // Link the best copy of the selected track
+            let selected_audio = list_audios.start().map(|audio| {
+                quality::better_copy(&audio, audio_list, &self.quality_policy).unwrap_or(audio)
+            });
             self.update_entry(
                 self.selected_mv.as_ref().unwrap().to_owned(),
                 selected_audio,
@@ -89,8 +109,50 @@ impl Updater {
         }
     }
 
+    /// Select an already linked MV and add another audio version to it
+    fn add_version(&mut self) {
+        clear_term("Select an MV to add an audio version to")
+            .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let mut linked = self
+            .audio_video
+            .borrow()
+            .keys()
+            .cloned()
+            .collect::<Vec<String>>();
+        linked.sort();
+        let fzf_view = FzfSelector::new(Some(linked), Some(vec!["[[Back]]".to_owned()]), None);
+        let selected_mv = fzf_view.fzf_select(SelectType::Single);
+        if selected_mv.is_empty() || selected_mv == "[[Back]]" {
+            return;
+        }
+        let list_audios = ListAudios::new(self.audio_found.clone().unwrap_or_default());
+        let Some(selected_audio) = list_audios.start() else {
+            return;
+        };
+        clear_term("Type a label for this version, for example Remaster, Japanese or Live")
+            .unwrap_or_else(|e| eprintln!("Couldn't clear terminal: {}", e));
+        let (label, _) =
+            FzfSelector::new(None, None, None).fzf_select_with_query(SelectType::Single);
+        if label.is_empty() {
+            return;
+        }
+        self.add_version_entry(&selected_mv, &label, selected_audio);
+    }
+
+    fn add_version_entry(&mut self, selected_mv: &str, label: &str, selected_audio: String) {
+        if !self.audio_video.borrow().contains_key(selected_mv) {
+            return;
+        }
+        self.meta
+            .borrow_mut()
+            .entry(unicode_paths::nfc(selected_mv))
+            .or_default()
+            .add_version(label, &selected_audio);
+    }
+
     /// Scan the mv_dir for videos that are not in the audio_video json file
-    /// and add them to the mvs_found list
+    /// and add them to the mvs_found list. Paths are compared in NFC so that a video
+    /// that is linked under a different normalization form is not listed
     ///
     /// # Panics
     /// Panics if the mv_dir does not exists
@@ -107,28 +169,16 @@ impl Updater {
             "{}",
             format!("{} is not a directory", mv_path.display())
         );
-        let video_exts = [
-            "mp4", "mkv", "avi", "webm", "ts", "flv", "wmv", "mov", "mpg", "mpeg",
-        ];
+        let linked = self
+            .audio_video
+            .borrow()
+            .keys()
+            .map(|k| unicode_paths::nfc(k))
+            .collect::<HashSet<String>>();
         self.mvs_found = Some(
-            mv_path
-                .read_dir()
-                .unwrap()
-                .filter_map(|entry| entry.ok())
-                .filter(|entry| {
-                    entry
-                        .path()
-                        .extension()
-                        .map(|ext| video_exts.contains(&ext.to_str().unwrap()))
-                        .unwrap_or(false)
-                })
-                .filter(|entry| {
-                    !self
-                        .audio_video
-                        .borrow_mut()
-                        .contains_key(entry.path().to_str().unwrap())
-                })
-                .map(|entry| entry.path().to_str().unwrap().to_string())
+            scan_video_files(&self.mv_dir)
+                .into_iter()
+                .filter(|path| !linked.contains(&unicode_paths::nfc(path)))
                 .collect(),
         );
     }
@@ -150,24 +200,47 @@ impl Updater {
             "{}",
             format!("{} is not a directory", audio_path.display())
         );
-        let walk_dir = WalkDir::new(audio_path).into_iter();
-        let audio_exts = ["mp3", "wav", "ogg", "flac", "m4a", "aac"];
-        self.audio_found = Some(
-            walk_dir
-                .filter_map(|entry| entry.ok())
-                .filter(|entry| {
-                    entry
-                        .path()
-                        .extension()
-                        .map(|ext| audio_exts.contains(&ext.to_str().unwrap()))
-                        .unwrap_or(false)
-                })
-                .map(|entry| entry.path().to_str().unwrap().to_string())
-                .collect(),
-        );
+        self.audio_found = Some(scan_audio_files(&self.audio_dir));
     }
 }
 
+/// List the video files in the directory, sub directories are not scanned
+pub fn scan_video_files(video_dir: &str) -> Vec<String> {
+    let video_exts = [
+        "mp4", "mkv", "avi", "webm", "ts", "flv", "wmv", "mov", "mpg", "mpeg",
+    ];
+    Path::new(video_dir)
+        .read_dir()
+        .unwrap()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .path()
+                .extension()
+                .map(|ext| video_exts.contains(&ext.to_str().unwrap()))
+                .unwrap_or(false)
+        })
+        .map(|entry| entry.path().to_str().unwrap().to_string())
+        .collect()
+}
+
+/// List the audio files in the directory and its sub directories
+pub fn scan_audio_files(audio_dir: &str) -> Vec<String> {
+    let walk_dir = WalkDir::new(audio_dir).into_iter();
+    let audio_exts = ["mp3", "wav", "ogg", "flac", "m4a", "aac"];
+    walk_dir
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .path()
+                .extension()
+                .map(|ext| audio_exts.contains(&ext.to_str().unwrap()))
+                .unwrap_or(false)
+        })
+        .map(|entry| entry.path().to_str().unwrap().to_string())
+        .collect()
+}
+
 struct ListAudios {
     audio_list: Vec<String>,
 }
@@ -197,6 +270,7 @@ impl ListAudios {
 }
 
 #[cfg(test)]
+#[allow(clippy::arc_with_non_send_sync)]
 mod tests {
     use super::*;
     use tempdir::TempDir;
@@ -219,6 +293,7 @@ mod tests {
             mv_dir.to_str().unwrap().to_string(),
             "".to_string(),
             Arc::new(RefCell::new(HashMap::new())),
+            Arc::new(RefCell::new(HashMap::new())),
         );
         updater.scan_mvs();
         assert_eq!(updater.mvs_found.as_ref().unwrap().len(), 1);
@@ -249,6 +324,7 @@ mod tests {
             "".to_string(),
             audio_dir.to_str().unwrap().to_string(),
             Arc::new(RefCell::new(HashMap::new())),
+            Arc::new(RefCell::new(HashMap::new())),
         );
         updater.scan_audio();
         assert_eq!(updater.audio_found.as_ref().unwrap().len(), 2);
@@ -267,7 +343,12 @@ mod tests {
     #[test]
     fn test_update_entry() {
         let rc = Arc::new(RefCell::new(HashMap::new()));
-        let mut updater = Updater::new("".to_string(), "".to_string(), rc.clone());
+        let mut updater = Updater::new(
+            "".to_string(),
+            "".to_string(),
+            rc.clone(),
+            Arc::new(RefCell::new(HashMap::new())),
+        );
         updater.audio_found = Some(vec!["audio.mp3".to_string()]);
         updater.mvs_found = Some(vec!["mv_0.mp4".to_string(), "mv_1.mp4".to_string()]);
         assert_eq!(rc.borrow().len(), 0);
@@ -278,4 +359,20 @@ mod tests {
             "audio.mp3".to_string()
         );
     }
+
+    #[test]
+    fn test_add_version_entry() {
+        let rc = Arc::new(RefCell::new(HashMap::new()));
+        let meta = Arc::new(RefCell::new(HashMap::new()));
+        let mut updater = Updater::new("".to_string(), "".to_string(), rc.clone(), meta.clone());
+        updater.add_version_entry("mv_0.mp4", "Live", "live.mp3".to_string());
+        assert!(meta.borrow().is_empty());
+        rc.borrow_mut()
+            .insert("mv_0.mp4".to_string(), "audio.mp3".to_string());
+        updater.add_version_entry("mv_0.mp4", "Live", "live.mp3".to_string());
+        let versions = meta.borrow().get("mv_0.mp4").unwrap().versions("audio.mp3");
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[1].label, "Live");
+        assert_eq!(versions[1].path, "live.mp3");
+    }
 }